@@ -0,0 +1,18 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use group_new::master::Master;
+use wca_oauth::{WcifContainer, WcifGenerator};
+
+fn master_new(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Master::new");
+    group.sample_size(10);
+    for competitors in [100, 500, 1000] {
+        let generator = WcifGenerator::new(0, competitors);
+        group.bench_with_input(BenchmarkId::from_parameter(competitors), &generator, |b, generator| {
+            b.iter_batched(|| WcifContainer::new(generator.generate()), |wcif| Master::new(wcif, "stage 16;"), BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, master_new);
+criterion_main!(benches);
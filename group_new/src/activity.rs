@@ -34,7 +34,7 @@ impl PreActivity {
                 }
                 let attempt: Option<usize> = iter.next().map(|s|s[1..].parse().unwrap());
                 let event = wcif.events_iter().find(|event| event.id == event_id).unwrap();
-                let events = if let Some(time_limit) = &event.rounds[0].time_limit {
+                let events = if let Some(time_limit) = event.rounds[0].time_limit.as_ref().filter(|t| !t.cumulative_round_ids.is_empty()) {
                     if used_shared.contains(&time_limit.cumulative_round_ids[0]) {
                         return None;
                     }
@@ -0,0 +1,64 @@
+pub mod event;
+pub mod competitor;
+pub mod matrix;
+pub mod activity;
+pub mod settings;
+pub mod master;
+
+#[cfg(test)]
+mod test {
+    use wca_oauth::{WcifContainer, WcifGenerator};
+
+    use crate::activity::PreActivity;
+    use crate::master::Master;
+    use crate::settings::Settings;
+
+    #[test]
+    fn generated() {
+        //Many seeds and sizes instead of a few hand picked wcifs. Below about 250 competitors some
+        //generated competitions do not have enough staff, which `Master::new` does not handle yet.
+        for seed in 0..32 {
+            let competitors = 250 + (seed as usize * 37) % 250;
+            let wcif = WcifContainer::new(WcifGenerator::new(seed, competitors).generate());
+            let master = Master::new(wcif, "stage 16;");
+            for act in master.activities.iter() {
+                assert_eq!(0, act.capacity);
+            }
+            for competitor in master.competitors.iter().filter_map(|c| c.as_ref()) {
+                let assignments: Vec<_> = competitor.assignemtns.ones().collect();
+                for (idx, a) in assignments.iter().enumerate() {
+                    for b in &assignments[idx + 1..] {
+                        assert!(!master.collision_matrix.does_collide(*a, *b), "{} is assigned to colliding activities", competitor.name);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cumulative_limits() {
+        let mut generator = WcifGenerator::new(0, 100);
+        generator.events = ["333", "333bf", "444bf", "555bf"].iter().map(|e| e.to_string()).collect();
+        let mut wcif = generator.generate();
+        //333 keeps its time limit without cumulative rounds.
+        for event in wcif.events.iter_mut() {
+            let time_limit = event.rounds[0].time_limit.as_mut().unwrap();
+            time_limit.cumulative_round_ids = match event.id.as_str() {
+                "333bf" => vec!["333bf-r1".to_string()],
+                "444bf" | "555bf" => vec!["444bf-r1".to_string(), "555bf-r1".to_string()],
+                _ => vec![]
+            };
+        }
+        let wcif = WcifContainer::new(wcif);
+        let mut competitors: Vec<_> = wcif.persons_iter().map(|p| crate::competitor::Competitor::new(p, wcif.date())).collect();
+        let settings = Settings::new("stage 16;", competitors.len());
+        let events: Vec<Vec<_>> = PreActivity::pre_activities(&wcif, &mut competitors, &settings).iter()
+            .map(|p| p.events.iter().map(|id| id.event.id().to_string()).collect())
+            .collect();
+        assert!(events.contains(&vec!["333".to_string()]));
+        assert_eq!(1, events.iter().filter(|e| e.contains(&"333bf".to_string())).count());
+        assert!(events.contains(&vec!["333bf".to_string()]));
+        assert_eq!(1, events.iter().filter(|e| e.contains(&"444bf".to_string()) || e.contains(&"555bf".to_string())).count());
+        assert!(events.contains(&vec!["444bf".to_string(), "555bf".to_string()]));
+    }
+}
@@ -1,9 +1,4 @@
-pub mod event;
-mod competitor;
-pub mod matrix;
-mod activity;
-mod settings;
-mod master;
+use group_new::master;
 
 fn main() {
     let wcif = wca_oauth::parse(std::fs::read_to_string("wcif.json").unwrap()).unwrap();
@@ -27,6 +27,11 @@ impl Stage {
     pub fn no_of_stages(&self) -> f32 {
         self.stages.len() as f32
     }
+
+    /// Names and sizes of the sub-stages, e.g. `Red-20` is `(Some("Red"), 20)`.
+    pub fn sub_stages(&self) -> &[(Option<String>, usize)] {
+        &self.stages
+    }
 }
 
 impl Settings {
@@ -137,4 +142,8 @@ impl Settings {
     pub fn no_of_stages(&self, idx: usize) -> f32 {
         self.stages[idx].no_of_stages()
     }
+
+    pub fn stage(&self, idx: usize) -> &Stage {
+        &self.stages[idx]
+    }
 }
\ No newline at end of file
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use crate::badge::StaffTask;
+use crate::font::Fonts;
+use crate::language::Language;
+use crate::page_layout::PaperSize;
+use crate::report::{Report, Column};
+
+/// Who competes and who helps in every group of a round.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundAssignments {
+    pub event: String,
+    pub round: usize,
+    /// Groups in order, starting with group 1.
+    pub groups: Vec<GroupAssignments>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct GroupAssignments {
+    /// Registrant ids of the competitors with their station.
+    pub competitors: Vec<(usize, Option<usize>)>,
+    /// Registrant ids of the staff with their task.
+    pub staff: Vec<(usize, StaffTask)>,
+}
+
+impl RoundAssignments {
+    /// From groups of registrant ids and stations, as they are given when generating scorecards.
+    pub fn from_groups(event: &str, round: usize, groups: &[Vec<(usize, usize)>]) -> RoundAssignments {
+        let groups = groups.iter()
+            .map(|group| GroupAssignments {
+                competitors: group.iter().map(|(id, station)| (*id, Some(*station))).collect(),
+                staff: vec![],
+            })
+            .collect();
+        RoundAssignments { event: event.to_string(), round, groups }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReportOptions {
+    pub paper: PaperSize,
+    pub fonts: Fonts,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        ReportOptions { paper: PaperSize::A4, fonts: Fonts::default() }
+    }
+}
+
+#[derive(Default)]
+struct PersonInRound {
+    group: Option<usize>,
+    station: Option<usize>,
+    staff: Vec<String>,
+}
+
+/// Sheets to hang on the wall, with every group of a round and its staff, followed by everyone in
+/// the round sorted by name. Every round starts on a new page.
+pub fn assignment_sheets_to_pdf(rounds: &[RoundAssignments], competition: &str, map: &HashMap<usize, String>, language: &Language, options: &ReportOptions) -> Vec<u8> {
+    let mut report = Report::new(competition, options.paper, &options.fonts, language.right_to_left);
+    let name = |id: &usize| map.get(id).cloned().unwrap_or_default();
+    let station = |station: &Option<usize>| station.map(|s| s.to_string()).unwrap_or_default();
+    let group_label = language.text("group");
+
+    for round in rounds {
+        let event = language.event(&round.event).unwrap_or(&round.event);
+        report.title = format!("{competition} | {event} | {} {}", language.text("round"), round.round);
+        report.new_page();
+
+        let competitor_columns = [
+            Column { header: language.text("station"), width: 0.15 },
+            Column { header: language.text("name"), width: 0.65 },
+            Column { header: language.text("competitor_id"), width: 0.2 },
+        ];
+        let staff_columns = [
+            Column { header: language.text("task"), width: 0.3 },
+            Column { header: language.text("name"), width: 0.7 },
+        ];
+        for (idx, group) in round.groups.iter().enumerate() {
+            let mut competitors = group.competitors.clone();
+            competitors.sort_by_key(|(id, station)| (*station, name(id)));
+            let rows: Vec<_> = competitors.iter()
+                .map(|(id, s)| vec![station(s), name(id), id.to_string()])
+                .collect();
+            report.table(&format!("{group_label} {}", idx + 1), &competitor_columns, &rows);
+
+            if !group.staff.is_empty() {
+                let mut staff: Vec<_> = group.staff.iter()
+                    .map(|(id, task)| vec![task.name(language).to_string(), name(id)])
+                    .collect();
+                staff.sort();
+                report.table(&format!("{group_label} {} | {}", idx + 1, language.text("staff")), &staff_columns, &staff);
+            }
+        }
+
+        //Everyone in the round once, with the group they compete in and the groups they help in.
+        let mut people: HashMap<usize, PersonInRound> = HashMap::new();
+        for (idx, group) in round.groups.iter().enumerate() {
+            for (id, s) in &group.competitors {
+                let person = people.entry(*id).or_default();
+                person.group = Some(idx + 1);
+                person.station = *s;
+            }
+            for (id, task) in &group.staff {
+                people.entry(*id).or_default().staff.push(format!("{} {}", task.name(language), idx + 1));
+            }
+        }
+        let mut rows: Vec<_> = people.into_iter()
+            .map(|(id, person)| vec![
+                name(&id),
+                id.to_string(),
+                person.group.map(|group| group.to_string()).unwrap_or_default(),
+                station(&person.station),
+                person.staff.join(", "),
+            ])
+            .collect();
+        rows.sort();
+        report.new_page();
+        report.table(language.text("by_name"), &[
+            Column { header: language.text("name"), width: 0.4 },
+            Column { header: language.text("competitor_id"), width: 0.12 },
+            Column { header: group_label, width: 0.12 },
+            Column { header: language.text("station"), width: 0.12 },
+            Column { header: language.text("staff"), width: 0.24 },
+        ], &rows);
+    }
+    report.pdf()
+}
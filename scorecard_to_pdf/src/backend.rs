@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use printpdf::{PdfDocumentReference, PdfDocument, Point, Mm, PdfPageIndex, PdfLayerIndex, Line, PdfLayerReference, LineDashPattern, Color, Greyscale, Rgb, CurTransMat};
+use printpdf::lopdf::{self, content::Operation, Dictionary, Object};
+use crate::font::{bundled, font_families, FontPDF};
+use crate::image::Image;
+use crate::scorecard_generator::Weight;
+
+/// Size of a point in mm.
+pub(crate) const PT: f64 = 25.4 / 72.0;
+
+/// A piece of text written with a single font.
+pub struct TextRun<'t> {
+    pub text: &'t str,
+    pub weight: Weight,
+    /// Index of the font in the fonts of its weight, where 0 is the bundled Helvetica.
+    pub face: usize,
+    /// The font file, for backends which embed fonts.
+    pub font: &'t [u8],
+}
+
+/// What `ScorecardGenerator` draws on. The generator places, mirrors and measures everything, so
+/// backends only get shapes and text in mm from the top left corner of the page.
+pub trait Backend {
+    /// Adds a page of the given width and height in mm and draws on it from now on.
+    fn add_page(&mut self, width: f64, height: f64);
+
+    /// Draws on an earlier page from now on.
+    fn set_page(&mut self, page: usize);
+
+    /// Lines through the points with the given thickness in pt. Dashes and the gaps between them
+    /// are given in pt as well.
+    fn stroke(&mut self, points: &[(f64, f64)], closed: bool, thickness: f64, dash: Option<(f64, f64)>);
+
+    /// Polygon filled with a colour given as red, green and blue from 0 to 1.
+    fn fill(&mut self, points: &[(f64, f64)], colour: (f64, f64, f64));
+
+    /// Writes the runs one after another, starting at `x` on the baseline `y`. The size is in pt.
+    fn text(&mut self, x: f64, y: f64, size: f64, runs: &[TextRun]);
+
+    /// Draws the image stretched to the given width and height, with its top left corner at `x`
+    /// and `y`.
+    fn image(&mut self, image: &Image, x: f64, y: f64, width: f64, height: f64);
+}
+
+/// The images of a document, each kept once, and which of them are used on every page.
+#[derive(Default)]
+struct ImageList {
+    images: Vec<Image>,
+    pages: Vec<Vec<usize>>,
+}
+
+impl ImageList {
+    fn add_page(&mut self) {
+        self.pages.push(vec![]);
+    }
+
+    /// Index of the image in the list, which is added to it the first time it is used.
+    fn add(&mut self, page: usize, image: &Image) -> usize {
+        let idx = match self.images.iter().position(|other| other.same(image)) {
+            Some(v) => v,
+            None => {
+                self.images.push(image.clone());
+                self.images.len() - 1
+            }
+        };
+        if !self.pages[page].contains(&idx) {
+            self.pages[page].push(idx);
+        }
+        idx
+    }
+}
+
+/// Draws into a pdf document, where fonts are embedded the first time they are used.
+pub struct PdfBackend {
+    document: PdfDocumentReference,
+    pages: Vec<(PdfPageIndex, PdfLayerIndex, f64)>,
+    page: usize,
+    fonts: HashMap<(Weight, usize), FontPDF>,
+    /// Printpdf adds a copy of the pixels for every use of an image, so images are only added to
+    /// the document once when it is saved, and the cards just refer to them.
+    images: ImageList,
+}
+
+impl PdfBackend {
+    pub fn new(title: &str) -> PdfBackend {
+        PdfBackend { document: PdfDocument::empty(title), pages: vec![], page: 0, fonts: HashMap::new(), images: ImageList::default() }
+    }
+
+    pub fn save<W: std::io::Write>(self, mut writer: W) -> std::io::Result<()> {
+        let bytes = self.document.save_to_bytes().map_err(|e| std::io::Error::other(e.to_string()))?;
+        if self.images.images.is_empty() {
+            return writer.write_all(&bytes);
+        }
+        let other = |e: lopdf::Error| std::io::Error::other(e.to_string());
+        let mut pdf = lopdf::Document::load_mem(&bytes).map_err(other)?;
+        let images: Vec<Object> = self.images.images.iter()
+            .map(|image| {
+                let mut stream: lopdf::Stream = image.xobject().into();
+                //Jpeg files are compressed already, which leaves the stream as it is.
+                let _ = stream.compress();
+                Object::Reference(pdf.add_object(stream))
+            })
+            .collect();
+        for ((_, page), page_images) in pdf.get_pages().into_iter().zip(&self.images.pages) {
+            if page_images.is_empty() {
+                continue;
+            }
+            let xobjects: Dictionary = page_images.iter().map(|idx| (image_name(*idx), images[*idx].clone())).collect();
+            let resources = pdf.get_dictionary(page).and_then(|page| page.get(b"Resources")).and_then(Object::as_reference).map_err(other)?;
+            pdf.get_object_mut(resources).and_then(Object::as_dict_mut).map_err(other)?.set("XObject", xobjects);
+        }
+        pdf.save_to(&mut writer)
+    }
+
+    pub fn into_bytes(self) -> std::io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.save(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Embeds the font the first time it is used. Fonts are checked when they are loaded, but
+    /// should printpdf still not read one, its text is written in the bundled font instead.
+    fn font(&mut self, weight: Weight, face: usize, bytes: &[u8]) -> FontPDF {
+        if let Some(font) = self.fonts.get(&(weight, face)) {
+            return font.clone();
+        }
+        let font = match self.document.add_external_font(bytes) {
+            Ok(font) => font,
+            Err(_) if face != 0 => self.font(weight, 0, bundled(weight)),
+            Err(e) => panic!("The bundled font can not be embedded: {e}"),
+        };
+        self.fonts.insert((weight, face), font.clone());
+        font
+    }
+
+    fn layer(&self) -> PdfLayerReference {
+        let (page, layer, _) = self.pages[self.page];
+        self.document.get_page(page).get_layer(layer)
+    }
+
+    /// Pdf coordinates start at the bottom of the page.
+    fn point(&self, (x, y): (f64, f64)) -> (Point, bool) {
+        let (_, _, height) = self.pages[self.page];
+        (Point::new(Mm(x), Mm(height - y)), false)
+    }
+}
+
+impl Backend for PdfBackend {
+    fn add_page(&mut self, width: f64, height: f64) {
+        let (page, layer) = self.document.add_page(Mm(width), Mm(height), "");
+        self.pages.push((page, layer, height));
+        self.images.add_page();
+        self.page = self.pages.len() - 1;
+        let layer = self.layer();
+        layer.set_overprint_stroke(true);
+        layer.set_outline_color(Color::Greyscale(Greyscale::new(0.0, None)));
+    }
+
+    fn set_page(&mut self, page: usize) {
+        self.page = page;
+    }
+
+    fn stroke(&mut self, points: &[(f64, f64)], closed: bool, thickness: f64, dash: Option<(f64, f64)>) {
+        let layer = self.layer();
+        let dash_pattern = match dash {
+            Some((dash, gap)) => {
+                let (dash, gap) = (Some(dash as i64), Some(gap as i64));
+                LineDashPattern::new(0, dash, gap, dash, gap, dash, gap)
+            }
+            None => LineDashPattern::new(0, None, None, None, None, None, None)
+        };
+        layer.set_line_dash_pattern(dash_pattern);
+        layer.set_outline_thickness(thickness);
+        layer.add_shape(Line {
+            points: points.iter().map(|point| self.point(*point)).collect(),
+            is_closed: closed,
+            has_fill: false,
+            has_stroke: true,
+            is_clipping_path: false,
+        });
+        if dash.is_some() {
+            layer.set_line_dash_pattern(LineDashPattern::new(0, None, None, None, None, None, None));
+        }
+    }
+
+    fn fill(&mut self, points: &[(f64, f64)], (r, g, b): (f64, f64, f64)) {
+        let layer = self.layer();
+        layer.set_fill_color(Color::Rgb(Rgb::new(r, g, b, None)));
+        layer.add_shape(Line {
+            points: points.iter().map(|point| self.point(*point)).collect(),
+            is_closed: true,
+            has_fill: true,
+            has_stroke: false,
+            is_clipping_path: false,
+        });
+        //Text is drawn with the fill colour as well.
+        layer.set_fill_color(Color::Greyscale(Greyscale::new(0.0, None)));
+    }
+
+    fn text(&mut self, x: f64, y: f64, size: f64, runs: &[TextRun]) {
+        let layer = self.layer();
+        let (cursor, _) = self.point((x, y));
+        layer.begin_text_section();
+        layer.set_text_cursor(cursor.x.into(), cursor.y.into());
+        for run in runs {
+            let font = self.font(run.weight, run.face, run.font);
+            layer.set_font(&font, size);
+            layer.write_text(run.text, &font);
+        }
+        layer.end_text_section();
+    }
+
+    fn image(&mut self, image: &Image, x: f64, y: f64, width: f64, height: f64) {
+        let idx = self.images.add(self.page, image);
+        let (_, _, page_height) = self.pages[self.page];
+        let layer = self.layer();
+        layer.save_graphics_state();
+        //Images are drawn on a square of 1 pt, which is stretched to the size and moved into place.
+        layer.set_ctm(CurTransMat::Raw([width / PT, 0.0, 0.0, height / PT, x / PT, (page_height - y - height) / PT]));
+        layer.add_operation(Operation::new("Do", vec![Object::Name(image_name(idx).into_bytes())]));
+        layer.restore_graphics_state();
+    }
+}
+
+/// Name of the image in the resources of the pages it is on.
+fn image_name(idx: usize) -> String {
+    format!("Image{idx}")
+}
+
+/// Draws every page as an svg image, e.g. for previews in a browser.
+#[derive(Default)]
+pub struct SvgBackend {
+    /// Width, height and content of every page.
+    pages: Vec<(f64, f64, String)>,
+    page: usize,
+    /// Images are embedded once in every page they are on, and used by their index in this list.
+    images: ImageList,
+}
+
+impl SvgBackend {
+    pub fn new() -> SvgBackend {
+        SvgBackend::default()
+    }
+
+    /// Every page as a complete svg document, sized in mm.
+    pub fn pages(self) -> Vec<String> {
+        let ImageList { images, pages: page_images } = self.images;
+        self.pages.into_iter()
+            .zip(page_images)
+            .map(|((width, height, content), page_images)| {
+                let defs: String = page_images.iter()
+                    .map(|idx| format!("<defs><image id=\"image{idx}\" href=\"{}\" width=\"1\" height=\"1\" preserveAspectRatio=\"none\"/></defs>\n", images[*idx].data_url()))
+                    .collect();
+                format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}mm\" height=\"{height}mm\" viewBox=\"0 0 {width} {height}\">\n\
+                    {defs}<rect width=\"{width}\" height=\"{height}\" fill=\"white\"/>\n{content}</svg>\n")
+            })
+            .collect()
+    }
+
+    fn content(&mut self) -> &mut String {
+        &mut self.pages[self.page].2
+    }
+}
+
+fn svg_points(points: &[(f64, f64)]) -> String {
+    points.iter().map(|(x, y)| format!("{x:.3},{y:.3}")).collect::<Vec<_>>().join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl Backend for SvgBackend {
+    fn add_page(&mut self, width: f64, height: f64) {
+        self.pages.push((width, height, String::new()));
+        self.images.add_page();
+        self.page = self.pages.len() - 1;
+    }
+
+    fn set_page(&mut self, page: usize) {
+        self.page = page;
+    }
+
+    fn stroke(&mut self, points: &[(f64, f64)], closed: bool, thickness: f64, dash: Option<(f64, f64)>) {
+        let tag = if closed { "polygon" } else { "polyline" };
+        let dash = match dash {
+            Some((dash, gap)) => format!(" stroke-dasharray=\"{:.3} {:.3}\"", dash * PT, gap * PT),
+            None => String::new()
+        };
+        let points = svg_points(points);
+        writeln!(self.content(), "<{tag} points=\"{points}\" fill=\"none\" stroke=\"black\" stroke-width=\"{:.3}\"{dash}/>", thickness * PT).unwrap();
+    }
+
+    fn fill(&mut self, points: &[(f64, f64)], (r, g, b): (f64, f64, f64)) {
+        let channel = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let points = svg_points(points);
+        writeln!(self.content(), "<polygon points=\"{points}\" fill=\"rgb({},{},{})\"/>", channel(r), channel(g), channel(b)).unwrap();
+    }
+
+    fn text(&mut self, x: f64, y: f64, size: f64, runs: &[TextRun]) {
+        //The text is already in visual order, so the viewer must not reorder it again.
+        let mut text = format!("<text x=\"{x:.3}\" y=\"{y:.3}\" font-size=\"{:.3}\" font-family=\"{}\" \
+            xml:space=\"preserve\" direction=\"ltr\" unicode-bidi=\"bidi-override\">", size * PT, font_families());
+        for run in runs {
+            let weight = match run.weight {
+                Weight::Normal => "normal",
+                Weight::Bold => "bold",
+            };
+            write!(text, "<tspan font-weight=\"{weight}\">{}</tspan>", escape(run.text)).unwrap();
+        }
+        text.push_str("</text>");
+        writeln!(self.content(), "{text}").unwrap();
+    }
+
+    fn image(&mut self, image: &Image, x: f64, y: f64, width: f64, height: f64) {
+        let idx = self.images.add(self.page, image);
+        writeln!(self.content(), "<use href=\"#image{idx}\" transform=\"translate({x:.3} {y:.3}) scale({width:.3} {height:.3})\"/>").unwrap();
+    }
+}
+
+/// Renders an svg page to a png image with the given resolution. Text is drawn with the given font
+/// files, so it looks like the pdf.
+#[cfg(feature = "png")]
+pub(crate) fn svg_to_png(svg: &str, fonts: &[&[u8]], dpi: f64) -> Result<Vec<u8>, String> {
+    use resvg::{tiny_skia, usvg};
+    let mut options = usvg::Options::default();
+    {
+        let database = options.fontdb_mut();
+        for font in fonts {
+            database.load_font_data(font.to_vec());
+        }
+    }
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| e.to_string())?;
+    //Svg pages are sized in mm, which usvg converts to pixels at 96 dpi.
+    let scale = (dpi / 96.0) as f32;
+    let size = tree.size().to_int_size().scale_by(scale).ok_or("Empty page")?;
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height()).ok_or("Empty page")?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use printpdf::lopdf::{Document, Object};
+    use crate::image::Image;
+    use crate::scorecard_generator::Weight;
+    use super::{Backend, PdfBackend, SvgBackend, TextRun};
+
+    const LOGO: &[u8] = include_bytes!("../tests/fixtures/rgb.jpg");
+
+    #[test]
+    fn images_embedded_once() {
+        let logo = Image::from_bytes(LOGO.to_vec()).unwrap();
+        let mut backend = PdfBackend::new("Logos");
+        for _ in 0..2 {
+            backend.add_page(210.0, 297.0);
+            for card in 0..4 {
+                backend.image(&logo, 10.0, 10.0 + 70.0 * card as f64, 20.0, 10.0);
+            }
+        }
+        let pdf = Document::load_mem(&backend.into_bytes().unwrap()).unwrap();
+        let images: Vec<_> = pdf.objects.iter()
+            .filter(|(_, object)| matches!(object, Object::Stream(stream) if stream.dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image")))
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(images.len(), 1);
+        for page in pdf.get_pages().into_values() {
+            let resources = pdf.get_dictionary(page).unwrap().get(b"Resources").unwrap().as_reference().unwrap();
+            let xobjects = pdf.get_dictionary(resources).unwrap().get(b"XObject").unwrap().as_dict().unwrap();
+            assert_eq!(xobjects.get(b"Image0").unwrap().as_reference().unwrap(), images[0]);
+            let content = pdf.get_and_decode_page_content(page).unwrap();
+            assert_eq!(content.operations.iter().filter(|operation| operation.operator == "Do").count(), 4);
+        }
+    }
+
+    #[test]
+    fn svg_font_families() {
+        let mut backend = SvgBackend::new();
+        backend.add_page(105.0, 99.0);
+        backend.text(10.0, 10.0, 12.0, &[TextRun { text: "Ana", weight: Weight::Normal, face: 0, font: &[] }]);
+        let svg = backend.pages().remove(0);
+        assert!(svg.contains("font-family=\"'Helvetica', 'Noto Sans', 'DejaVu Sans', 'Arial', "), "{svg}");
+        assert!(svg.contains("'Noto Sans Thai', sans-serif\""), "{svg}");
+    }
+}
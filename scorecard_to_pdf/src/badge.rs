@@ -0,0 +1,188 @@
+use crate::font::Fonts;
+use crate::language::Language;
+use crate::page_layout::{PageLayout, CARD_WIDTH};
+use crate::scorecard_generator::{ScorecardGenerator, TextFit, Overflow, Alignment::*, Weight::*};
+
+/// Name tag for a single person.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Badge {
+    pub name: String,
+    pub wca_id: Option<String>,
+    pub country: String,
+    pub role: BadgeRole,
+    /// Rows of the assignment table, in the order they are printed.
+    pub assignments: Vec<BadgeAssignment>,
+}
+
+/// The role printed at the top of a badge. Every role has its own colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BadgeRole {
+    Competitor,
+    Delegate,
+    TraineeDelegate,
+    Organizer,
+    Staff,
+}
+
+impl BadgeRole {
+    fn colour(&self) -> (f64, f64, f64) {
+        match self {
+            BadgeRole::Competitor => (0.85, 0.85, 0.85),
+            BadgeRole::Delegate => (0.55, 0.75, 1.0),
+            BadgeRole::TraineeDelegate => (0.75, 0.88, 1.0),
+            BadgeRole::Organizer => (0.65, 0.9, 0.65),
+            BadgeRole::Staff => (1.0, 0.85, 0.45),
+        }
+    }
+
+    fn label_key(&self) -> &'static str {
+        match self {
+            BadgeRole::Competitor => "competitor",
+            BadgeRole::Delegate => "delegate",
+            BadgeRole::TraineeDelegate => "trainee_delegate",
+            BadgeRole::Organizer => "organizer",
+            BadgeRole::Staff => "staff",
+        }
+    }
+}
+
+/// What a person does in a single round: the group they compete in and the groups they help in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BadgeAssignment {
+    pub event: String,
+    pub round: usize,
+    pub competing: Option<usize>,
+    pub staff: Vec<(StaffTask, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StaffTask {
+    Judge,
+    Scrambler,
+    Runner,
+    DataEntry,
+    Announcer,
+    Other(String),
+}
+
+impl StaffTask {
+    /// Short form used in the assignment table, explained by the legend at the bottom of the badge.
+    pub(crate) fn letter(&self) -> String {
+        match self {
+            StaffTask::Judge => "J".to_string(),
+            StaffTask::Scrambler => "S".to_string(),
+            StaffTask::Runner => "R".to_string(),
+            StaffTask::DataEntry => "D".to_string(),
+            StaffTask::Announcer => "A".to_string(),
+            StaffTask::Other(task) => task.chars().next().map(|c| c.to_uppercase().to_string()).unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn name<'a>(&'a self, language: &'a Language) -> &'a str {
+        match self {
+            StaffTask::Judge => language.text("judge"),
+            StaffTask::Scrambler => language.text("scrambler"),
+            StaffTask::Runner => language.text("runner"),
+            StaffTask::DataEntry => language.text("data_entry"),
+            StaffTask::Announcer => language.text("announcer"),
+            StaffTask::Other(task) => task,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BadgeOptions {
+    pub layout: PageLayout,
+    pub fonts: Fonts,
+    /// Number of columns the assignment table is split into, so badges of people with many
+    /// assignments still fit.
+    pub assignment_columns: usize,
+}
+
+impl Default for BadgeOptions {
+    fn default() -> Self {
+        BadgeOptions { layout: PageLayout::default(), fonts: Fonts::default(), assignment_columns: 2 }
+    }
+}
+
+/// The badges as a single pdf.
+pub fn badges_to_pdf(badges: &[Badge], competition: &str, language: &Language, options: &BadgeOptions) -> Vec<u8> {
+    let mut generator = ScorecardGenerator::new(competition, &options.layout, &options.fonts, language.right_to_left);
+    let per_page = options.layout.per_page();
+    for (idx, badge) in badges.iter().enumerate() {
+        generator.set_page(idx / per_page);
+        generator.set_position(idx % per_page);
+        draw_badge(&mut generator, badge, language, options.assignment_columns.max(1));
+    }
+    generator.into_backend().into_bytes().unwrap()
+}
+
+fn draw_badge(generator: &mut ScorecardGenerator, badge: &Badge, language: &Language, columns: usize) {
+    let middle = CARD_WIDTH / 2.0;
+    generator.fill_square(0.0, 0.0, CARD_WIDTH, 14.0, badge.role.colour());
+    generator.write(language.text(badge.role.label_key()), middle, 9.5, 14.0, Center, Bold);
+
+    let competition = generator.get_competition_name().to_string();
+    generator.write(&competition, middle, 20.0, 9.0, Center, Normal);
+
+    //Long names are made smaller until they fit, and only cut off if they are still too long.
+    let fit = TextFit { max_width: CARD_WIDTH - 10.0, min_size: 8.0, overflow: Overflow::Ellipsis };
+    generator.write_fitted(middle, 31.0, 18.0, Center, &[(&badge.name, Bold)], fit);
+    let details = match &badge.wca_id {
+        Some(id) => format!("{id} | {}", badge.country),
+        None => badge.country.clone(),
+    };
+    generator.write(&details, middle, 38.0, 10.0, Center, Normal);
+
+    draw_assignments(generator, &badge.assignments, language, columns);
+}
+
+fn draw_assignments(generator: &mut ScorecardGenerator, assignments: &[BadgeAssignment], language: &Language, columns: usize) {
+    if assignments.is_empty() {
+        return;
+    }
+    let (left, top, row_height, size) = (5.0, 43.0, 5.0, 6.5);
+    let column_width = (CARD_WIDTH - 2.0 * left) / columns as f64;
+    let rows = assignments.len().div_ceil(columns);
+    let (event_width, group_width) = (column_width * 0.55, column_width * 0.15);
+    let staff_width = column_width - event_width - group_width;
+
+    for column in 0..columns.min(assignments.len()) {
+        let x = left + column as f64 * column_width;
+        generator.write(language.text("group"), x + event_width + group_width / 2.0, top - 1.0, size, Center, Bold);
+        generator.write(language.text("staff"), x + event_width + group_width + staff_width / 2.0, top - 1.0, size, Center, Bold);
+    }
+    for (idx, assignment) in assignments.iter().enumerate() {
+        let x = left + (idx / rows) as f64 * column_width;
+        let y = top + (idx % rows) as f64 * row_height;
+        let event = language.event(&assignment.event).unwrap_or(&assignment.event);
+        let event = match assignment.round {
+            1 => event.to_string(),
+            round => format!("{event} ({} {round})", language.text("round")),
+        };
+        let competing = assignment.competing.map(|group| group.to_string()).unwrap_or_default();
+        let staff = assignment.staff.iter()
+            .map(|(task, group)| format!("{}{group}", task.letter()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        generator.draw_square(x, y, event_width, row_height);
+        generator.draw_square(x + event_width, y, group_width, row_height);
+        generator.draw_square(x + event_width + group_width, y, staff_width, row_height);
+        generator.write(&event, x + 1.0, y + row_height - 1.5, size, Left, Normal);
+        generator.write(&competing, x + event_width + group_width / 2.0, y + row_height - 1.5, size, Center, Normal);
+        generator.write(&staff, x + event_width + group_width + 1.0, y + row_height - 1.5, size, Left, Normal);
+    }
+
+    //Explains the letters of the tasks which appear on this badge.
+    let mut tasks: Vec<&StaffTask> = vec![];
+    for (task, _) in assignments.iter().flat_map(|assignment| &assignment.staff) {
+        if !tasks.contains(&task) {
+            tasks.push(task);
+        }
+    }
+    let legend = tasks.iter()
+        .map(|task| format!("{}: {}", task.letter(), task.name(language)))
+        .collect::<Vec<_>>()
+        .join("   ");
+    generator.write(&legend, left, top + rows as f64 * row_height + 4.0, 5.5, Left, Normal);
+}
@@ -0,0 +1,182 @@
+use unicode_bidi::{BidiInfo, Level};
+
+/// How an Arabic letter connects to its neighbours.
+#[derive(Clone, Copy, PartialEq)]
+enum Joining {
+    /// Only connects to the letter before it, e.g. alef and waw.
+    Right,
+    /// Connects on both sides.
+    Dual,
+}
+
+/// Arabic and Persian letters with the code point of their isolated presentation form. The final,
+/// initial and medial forms follow directly after, for the forms the letter has.
+const ARABIC_FORMS: [(char, u32, Joining); 42] = [
+    ('\u{0622}', 0xFE81, Joining::Right),
+    ('\u{0623}', 0xFE83, Joining::Right),
+    ('\u{0624}', 0xFE85, Joining::Right),
+    ('\u{0625}', 0xFE87, Joining::Right),
+    ('\u{0626}', 0xFE89, Joining::Dual),
+    ('\u{0627}', 0xFE8D, Joining::Right),
+    ('\u{0628}', 0xFE8F, Joining::Dual),
+    ('\u{0629}', 0xFE93, Joining::Right),
+    ('\u{062A}', 0xFE95, Joining::Dual),
+    ('\u{062B}', 0xFE99, Joining::Dual),
+    ('\u{062C}', 0xFE9D, Joining::Dual),
+    ('\u{062D}', 0xFEA1, Joining::Dual),
+    ('\u{062E}', 0xFEA5, Joining::Dual),
+    ('\u{062F}', 0xFEA9, Joining::Right),
+    ('\u{0630}', 0xFEAB, Joining::Right),
+    ('\u{0631}', 0xFEAD, Joining::Right),
+    ('\u{0632}', 0xFEAF, Joining::Right),
+    ('\u{0633}', 0xFEB1, Joining::Dual),
+    ('\u{0634}', 0xFEB5, Joining::Dual),
+    ('\u{0635}', 0xFEB9, Joining::Dual),
+    ('\u{0636}', 0xFEBD, Joining::Dual),
+    ('\u{0637}', 0xFEC1, Joining::Dual),
+    ('\u{0638}', 0xFEC5, Joining::Dual),
+    ('\u{0639}', 0xFEC9, Joining::Dual),
+    ('\u{063A}', 0xFECD, Joining::Dual),
+    ('\u{0641}', 0xFED1, Joining::Dual),
+    ('\u{0642}', 0xFED5, Joining::Dual),
+    ('\u{0643}', 0xFED9, Joining::Dual),
+    ('\u{0644}', 0xFEDD, Joining::Dual),
+    ('\u{0645}', 0xFEE1, Joining::Dual),
+    ('\u{0646}', 0xFEE5, Joining::Dual),
+    ('\u{0647}', 0xFEE9, Joining::Dual),
+    ('\u{0648}', 0xFEED, Joining::Right),
+    ('\u{0649}', 0xFEEF, Joining::Right),
+    ('\u{064A}', 0xFEF1, Joining::Dual),
+    ('\u{067E}', 0xFB56, Joining::Dual),
+    ('\u{0686}', 0xFB7A, Joining::Dual),
+    ('\u{0698}', 0xFB8A, Joining::Right),
+    ('\u{06A9}', 0xFB8E, Joining::Dual),
+    ('\u{06AF}', 0xFB92, Joining::Dual),
+    ('\u{06CC}', 0xFBFC, Joining::Dual),
+    //Tatweel only stretches the connection, it has no forms of its own.
+    ('\u{0640}', 0x0640, Joining::Dual),
+];
+
+const TATWEEL: char = '\u{0640}';
+const LAM: char = '\u{0644}';
+
+/// Lam followed by one of these alefs is written as a single ligature, given by its isolated form.
+const LAM_ALEF: [(char, u32); 4] = [
+    ('\u{0622}', 0xFEF5),
+    ('\u{0623}', 0xFEF7),
+    ('\u{0625}', 0xFEF9),
+    ('\u{0627}', 0xFEFB),
+];
+
+fn joining(char: char) -> Option<(u32, Joining)> {
+    ARABIC_FORMS.iter().find(|(c, _, _)| *c == char).map(|(_, form, joining)| (*form, *joining))
+}
+
+/// Harakat and other marks are written on top of letters and do not break the connection.
+fn is_transparent(char: char) -> bool {
+    matches!(char, '\u{064B}'..='\u{065F}' | '\u{0670}')
+}
+
+/// Replaces Arabic letters with the presentation form matching their position in the word, as
+/// fonts without shaping support only draw the isolated forms otherwise.
+pub fn shape_arabic(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let neighbour = |idx: usize, step: isize| {
+        let mut idx = idx as isize + step;
+        while idx >= 0 && (idx as usize) < chars.len() {
+            if !is_transparent(chars[idx as usize]) {
+                return Some(idx as usize);
+            }
+            idx += step;
+        }
+        None
+    };
+    let mut shaped = String::with_capacity(text.len());
+    let mut skip = None;
+    for (idx, char) in chars.iter().enumerate() {
+        if skip == Some(idx) {
+            continue;
+        }
+        let (isolated, joins) = match joining(*char) {
+            Some(v) if *char != TATWEEL => v,
+            _ => {
+                shaped.push(*char);
+                continue;
+            }
+        };
+        let prev = neighbour(idx, -1).and_then(|i| joining(chars[i])).is_some_and(|(_, j)| j == Joining::Dual);
+        let next_idx = neighbour(idx, 1);
+        let next = next_idx.and_then(|i| joining(chars[i])).is_some() && joins == Joining::Dual;
+
+        if *char == LAM {
+            if let Some((alef, ligature)) = next_idx.and_then(|i| LAM_ALEF.iter().find(|(c, _)| *c == chars[i]).map(|(_, l)| (i, *l))) {
+                shaped.push(char::from_u32(ligature + prev as u32).unwrap());
+                skip = Some(alef);
+                continue;
+            }
+        }
+        let offset = match (prev, next) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (false, true) => 2,
+            (true, true) => 3,
+        };
+        shaped.push(char::from_u32(isolated + offset).unwrap());
+    }
+    shaped
+}
+
+/// Shapes the text and reorders it from logical to visual order, i.e. the order the characters are
+/// written in from left to right. Without a direction it is taken from the first strong character.
+pub fn visual_order(text: &str, right_to_left: bool) -> String {
+    let shaped = shape_arabic(text);
+    let level = right_to_left.then(Level::rtl);
+    let info = BidiInfo::new(&shaped, level);
+    if !info.has_rtl() {
+        return shaped;
+    }
+    info.paragraphs.iter()
+        .map(|paragraph| info.reorder_line(paragraph, paragraph.range.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{shape_arabic, visual_order};
+
+    #[test]
+    fn positional_forms() {
+        //Beh is initial, medial and final. Alef only connects to the letter before it.
+        assert_eq!(shape_arabic("ببب"), "\u{FE91}\u{FE92}\u{FE90}");
+        assert_eq!(shape_arabic("باب"), "\u{FE91}\u{FE8E}\u{FE8F}");
+        assert_eq!(shape_arabic("ب"), "\u{FE8F}");
+        assert_eq!(shape_arabic("ب ب"), "\u{FE8F} \u{FE8F}");
+        assert_eq!(shape_arabic("Ana 12"), "Ana 12");
+    }
+
+    #[test]
+    fn transparent_marks() {
+        assert_eq!(shape_arabic("بَب"), "\u{FE91}\u{064E}\u{FE90}");
+        assert_eq!(shape_arabic("بـ"), "\u{FE91}\u{0640}");
+    }
+
+    #[test]
+    fn lam_alef() {
+        assert_eq!(shape_arabic("لا"), "\u{FEFB}");
+        assert_eq!(shape_arabic("لأ"), "\u{FEF7}");
+        assert_eq!(shape_arabic("لإ"), "\u{FEF9}");
+        assert_eq!(shape_arabic("لآ"), "\u{FEF5}");
+        //Connected to the letter before it, and the letter after the ligature does not connect to it.
+        assert_eq!(shape_arabic("سلام"), "\u{FEB3}\u{FEFC}\u{FEE1}");
+        //Lam before another letter is shaped as usual.
+        assert_eq!(shape_arabic("لب"), "\u{FEDF}\u{FE90}");
+    }
+
+    #[test]
+    fn reordering() {
+        assert_eq!(visual_order("Ana Smith", false), "Ana Smith");
+        assert_eq!(visual_order("باب", false), "\u{FE8F}\u{FE8E}\u{FE91}");
+        assert_eq!(visual_order("باب 12", true), "12 \u{FE8F}\u{FE8E}\u{FE91}");
+        assert_eq!(visual_order("Ana باب", false), "Ana \u{FE8F}\u{FE8E}\u{FE91}");
+    }
+}
@@ -0,0 +1,126 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+use crate::scorecard::{OutputKind, TruncatedText};
+
+/// How scorecards are split into documents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DocumentSplit {
+    /// A single document, where every stage starts on a new page.
+    Single,
+    /// A document per stage, so every stage can be printed on its own.
+    #[default]
+    ByStage,
+}
+
+/// A single pdf of a bundle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScorecardDocument {
+    /// File name, as given by the naming template of the options.
+    pub name: String,
+    /// Name or number of the stage of all scorecards in the document, if they are on the same one.
+    pub stage: Option<String>,
+    /// Ids of the events in the document, in the order they first appear.
+    pub events: Vec<String>,
+    pub pages: usize,
+    pub pdf: Vec<u8>,
+}
+
+/// All documents made from a set of scorecards.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ScorecardBundle {
+    pub documents: Vec<ScorecardDocument>,
+    /// Texts which had to be cut off to fit on their scorecards.
+    pub warnings: Vec<TruncatedText>,
+}
+
+impl ScorecardBundle {
+    /// What `write` writes: a pdf if there is a single document, and otherwise a zip.
+    pub fn output_kind(&self) -> OutputKind {
+        match self.documents.len() {
+            1 => OutputKind::Pdf,
+            _ => OutputKind::Zip
+        }
+    }
+
+    /// Writes a pdf if there is a single document, and otherwise a zip with all of them.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<OutputKind> {
+        match self.documents.as_slice() {
+            [document] => writer.write_all(&document.pdf)?,
+            _ => {
+                self.write_zip(writer)?;
+            }
+        }
+        Ok(self.output_kind())
+    }
+
+    /// A zip with every document, named by their names.
+    pub fn write_zip<W: Write>(&self, writer: W) -> io::Result<W> {
+        let mut zip = ZipWriter::new_stream(writer);
+        for document in &self.documents {
+            zip.start_file(document.name.as_str(), zip_options())?;
+            zip.write_all(&document.pdf)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+
+    /// Writes every document as a file into the directory, which is created if needed. Returns
+    /// the paths of the files.
+    pub fn write_to_directory(&self, directory: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+        let directory = directory.as_ref();
+        std::fs::create_dir_all(directory)?;
+        self.documents.iter()
+            .map(|document| {
+                let path = directory.join(&document.name);
+                std::fs::write(&path, &document.pdf)?;
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+/// Zips are written front to back, so they can go to writers which can not seek, such as a http
+/// response. Pdfs are already compressed, so they are stored as they are.
+pub(crate) fn zip_options() -> SimpleFileOptions {
+    SimpleFileOptions::default().compression_method(CompressionMethod::Stored)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+    use crate::scorecard::OutputKind;
+    use super::{ScorecardBundle, ScorecardDocument};
+
+    fn document(name: &str, pdf: &[u8]) -> ScorecardDocument {
+        ScorecardDocument { name: name.to_string(), stage: None, events: vec![], pages: 1, pdf: pdf.to_vec() }
+    }
+
+    #[test]
+    fn zip_round_trip() {
+        let bundle = ScorecardBundle {
+            documents: vec![document("Red_scorecards.pdf", b"first file"), document("Blå_scorecards.pdf", b"second file")],
+            warnings: vec![],
+        };
+        //A Vec behind a reference can not seek, like a http response.
+        let mut bytes = vec![];
+        assert_eq!(bundle.write(&mut bytes).unwrap(), OutputKind::Zip);
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 2);
+        for (idx, document) in bundle.documents.iter().enumerate() {
+            let mut file = archive.by_index(idx).unwrap();
+            assert_eq!(file.name(), document.name);
+            let mut read = vec![];
+            file.read_to_end(&mut read).unwrap();
+            assert_eq!(read, document.pdf);
+        }
+    }
+
+    #[test]
+    fn single_document() {
+        let bundle = ScorecardBundle { documents: vec![document("Red_scorecards.pdf", b"%PDF")], warnings: vec![] };
+        let mut bytes = vec![];
+        assert_eq!(bundle.write(&mut bytes).unwrap(), OutputKind::Pdf);
+        assert_eq!(bytes, b"%PDF");
+    }
+}
@@ -1,78 +1,216 @@
 use std::collections::HashMap;
+use qrcode::{QrCode, EcLevel, Color};
 use crate::language::Language;
+use crate::scorecard::hex_colour;
 use crate::scorecard::MaybeScorecard;
-use crate::scorecard_generator::ScorecardGenerator;
-use crate::TimeLimit;
+use crate::scorecard_generator::{ScorecardGenerator, TextFit, Overflow};
+use crate::backend::Backend;
+use crate::{TimeLimit, ScorecardOptions};
 use crate::scorecard_generator::{Alignment::*, Weight::*};
+use crate::template::{Element, AttemptRows, GridElement, QrCodeElement};
 
-pub fn draw_scorecard(generator: &mut ScorecardGenerator, scorecard: &MaybeScorecard, map: &HashMap<usize, String>, limits: &HashMap<&str, TimeLimit>, language: &Language) {
+/// Draws the scorecard and returns the texts which had to be cut off to fit. Templates with a sheet
+/// per attempt are drawn for the given attempt.
+pub fn draw_scorecard<B: Backend>(generator: &mut ScorecardGenerator<B>, scorecard: &MaybeScorecard, attempt: Option<usize>, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: &Language, options: &ScorecardOptions) -> Vec<String> {
+    let template = options.template_for(scorecard.event());
+    let competition_id = &options.competition_id;
     let get_event = get_event_func(language);
-    //Competiton
-    let name = generator.get_competition_name().to_string();
-    generator.write(&name, 52.5, 7.0, 10.0, Center, Normal);
-    let round_text = format!("{}: {} | ", language.round, scorecard.round());
-    let event_text = format!("{}", get_event(scorecard.event()));
-    let group_text = format!(" | {}: {}", language.group, scorecard.group());
-    generator.write_multi_text(52.5, 11.5, 10.0, Center, &[
-        (&round_text, Normal),
-        (&event_text, Bold),
-        (&group_text, Normal),
-    ]);
-    generator.draw_square(5.0, 15.0, 10.0, 5.5);
-    generator.write(&scorecard.id(), 10.0, 19.0, 10.0, Center, Normal);
-    generator.draw_square(15.0, 15.0, 85.0, 5.5);
-    generator.write(scorecard.name(map), 16.0, 19.0, 10.0, Left, Normal);
-
-    let attempts_amount = match scorecard.event() {
-        "666" | "777" | "333mbf" | "333bf" | "444bf" | "555bf" => 3,
-        _ => 5
+    let limit = match scorecard.limit(limits) {
+        TimeLimit::Single(z) => format!("{}: {}", language.text("time_limit"), time_string(*z)),
+        TimeLimit::Cumulative(z) => format!("{}: {}", language.text("cumulative_limit"), time_string(*z)),
+        TimeLimit::Cutoff(x, z) => format!("{}: {}, {}: {}", cutoff_label(language, scorecard.format().cutoff), time_string(*x), language.text("time_limit"), time_string(*z)),
+        TimeLimit::SharedCumulative(z, vec) => format!("{}: {} {} {}", language.text("cumulative_limit"), time_string(*z), language.text("for_scl"), vec.iter().map(|x|get_event(x)).collect::<Vec<_>>().join(&format!(" {} ", language.text("and_scl")))),
+        TimeLimit::Multi => multi_blind_text(language),
+        TimeLimit::None => format!("")
+    };
+    let station = match scorecard.station() {
+        Some(v) => v.to_string(),
+        None => "".to_string()
     };
+    let stage = scorecard.stage();
+    let competition = generator.get_competition_name().to_string();
+    let fill = |text: &str| fill_placeholders(text, |key| match key {
+        "competition" => Some(competition.clone()),
+        "competition_id" => Some(competition_id.clone()),
+        "event" => Some(get_event(scorecard.event()).to_string()),
+        "event_id" => Some(scorecard.event().to_string()),
+        "round" => Some(scorecard.round()),
+        "group" => Some(scorecard.group()),
+        "id" => Some(scorecard.id()),
+        "name" => Some(scorecard.name(map).to_string()),
+        "station" => Some(station.clone()),
+        "stage" => Some(stage.map(|stage| stage.label()).unwrap_or_default()),
+        "limit" => Some(limit.clone()),
+        "mbf_limit" => Some(multi_blind_text(language)),
+        "attempt" => Some(attempt.map_or("__".to_string(), |attempt| attempt.to_string())),
+        key => key.strip_prefix("lang.").and_then(|key| language.label(key)).map(str::to_string)
+    });
 
-    let height = 8.2;
-    let distance = 8.8;
-    let sign_box_width = 10.0;
-    let mut attempts_start_height = 25.5;
-    generator.write(&language.scram, 9.0 + sign_box_width / 2.0, attempts_start_height - 1.0, 7.0, Center, Normal);
-    generator.write(&language.result, (12.0 + 97.0 - sign_box_width) / 2.0, attempts_start_height - 1.0, 7.0, Center, Normal);
-    generator.write(&language.judge, 100.0 - sign_box_width - (sign_box_width / 2.0), attempts_start_height - 1.0, 7.0, Center, Normal);
-    generator.write(&language.comp, 100.0 - (sign_box_width / 2.0), attempts_start_height - 1.0, 7.0, Center, Normal);
+    let mut cut = vec![];
+    for element in &template.elements {
+        match element {
+            Element::Text(text) => {
+                let parts: Vec<_> = text.parts.iter().map(|part| (fill(&part.text), part.weight)).collect();
+                let parts: Vec<_> = parts.iter().map(|(string, weight)| (string.as_str(), *weight)).collect();
+                match text.fit() {
+                    Some(fit) => {
+                        if generator.write_fitted(text.x, text.y, text.size, text.align, &parts, fit) {
+                            cut.push(parts.iter().map(|(string, _)| *string).collect());
+                        }
+                    }
+                    None => generator.write_multi_text(text.x, text.y, text.size, text.align, &parts),
+                }
+            }
+            Element::Box(square) => generator.draw_square(square.x, square.y, square.width, square.height),
+            Element::AttemptRows(rows) => cut.extend(draw_attempt_rows(generator, scorecard, attempt, rows, &fill)),
+            Element::Grid(grid) => draw_grid(generator, grid),
+            Element::QrCode(qr) if *scorecard != MaybeScorecard::Blank => draw_qr_code(generator, qr, &fill(&qr.payload)),
+            Element::QrCode(_) => (),
+            Element::Logo(logo) => {
+                if let Some(image) = &options.logo {
+                    generator.draw_image(image, logo.x, logo.y, logo.width, logo.height);
+                }
+            }
+            Element::Image(element) => {
+                if let Some(image) = options.images.get(&element.name) {
+                    generator.draw_image(image, element.x, element.y, element.width, element.height);
+                }
+            }
+            Element::StageStripe(stripe) => {
+                if let Some(colour) = stage.and_then(|stage| stage.colour).and_then(hex_colour) {
+                    generator.fill_square(stripe.x, stripe.y, stripe.width, stripe.height, colour);
+                }
+            }
+        }
+    }
+    cut
+}
+
+/// Returns the headers which had to be cut off to fit above their columns. Sheets for a single
+/// attempt only have the row of that attempt.
+fn draw_attempt_rows<B: Backend>(generator: &mut ScorecardGenerator<B>, scorecard: &MaybeScorecard, attempt: Option<usize>, rows: &AttemptRows, fill: &dyn Fn(&str) -> String) -> Vec<String> {
+    let format = scorecard.format();
+    let attempts_amount = format.attempts;
+
+    let mut cut = vec![];
+    let mut x = rows.x;
+    for column in &rows.columns {
+        let header = fill(&column.header);
+        let fit = TextFit { max_width: column.width - 0.5, min_size: rows.header_size * 2.0 / 3.0, overflow: Overflow::Ellipsis };
+        if generator.write_fitted(x + column.width / 2.0, rows.y - 1.0, rows.header_size, Center, &[(&header, Normal)], fit) {
+            cut.push(header);
+        }
+        x += column.width;
+    }
+    //Attempt numbers are kept left of the first column.
+    let number_fit = TextFit { max_width: rows.x - rows.number_x - 0.5, min_size: rows.number_size * 2.0 / 3.0, overflow: Overflow::Ellipsis };
+    let draw_row = |generator: &mut ScorecardGenerator<B>, y: f64, number: &str| {
+        generator.write_fitted(rows.number_x, y - 2.0 + rows.height, rows.number_size, Left, &[(number, Normal)], number_fit);
+        let mut x = rows.x;
+        for column in &rows.columns {
+            generator.draw_square(x, y, column.width, rows.height);
+            x += column.width;
+        }
+    };
+    if let Some(attempt) = attempt {
+        draw_row(generator, rows.y, &attempt.to_string());
+        return cut;
+    }
     for i in 0..attempts_amount {
-        let j = i as f64;
-        generator.draw_square(9.0, attempts_start_height + j * distance, sign_box_width, height);
-        generator.write((i + 1).to_string().as_str(), 5.0, attempts_start_height - 2.0 + j * distance + height, 12.0, Left, Normal);
-        generator.draw_square(9.0 + sign_box_width, attempts_start_height + j * distance, 91.0 - 3.0 * sign_box_width, height);
-        generator.draw_square(100.0 - 2.0 * sign_box_width, attempts_start_height + j * distance, sign_box_width, height);
-        generator.draw_square(100.0 - sign_box_width, attempts_start_height + j * distance, sign_box_width, height);
+        draw_row(generator, rows.y + i as f64 * rows.distance, &(i + 1).to_string());
+    }
+    //Divider in the gap below the last attempt before the cutoff, from the attempt number to the
+    //end of the row.
+    if let Some(cutoff) = format.cutoff {
+        let y = rows.y + (cutoff - 1) as f64 * rows.distance + (rows.height + rows.distance) / 2.0;
+        let end = rows.x + rows.columns.iter().map(|column| column.width).sum::<f64>();
+        generator.draw_line(rows.number_x, y, end, y, 2.0);
     }
 
-    attempts_start_height += attempts_amount as f64 * distance + 3.8;
-    generator.write(&language.extra_attempts, 52.5, attempts_start_height - 1.0, 7.0, Center, Normal);
-    for i in 0..2 {
-        let j = i as f64;
-        generator.draw_square(9.0, attempts_start_height + j * distance, sign_box_width, height);
-        generator.write("_", 5.0, attempts_start_height - 2.0 + j * distance + height, 12.0, Left, Normal);
-        generator.draw_square(9.0 + sign_box_width, attempts_start_height + j * distance, 91.0 - 3.0 * sign_box_width, height);
-        generator.draw_square(100.0 - 2.0 * sign_box_width, attempts_start_height + j * distance, sign_box_width, height);
-        generator.draw_square(100.0 - sign_box_width, attempts_start_height + j * distance, sign_box_width, height);
+    if let Some(extra) = &rows.extra {
+        let extra_start = rows.y + attempts_amount as f64 * rows.distance + extra.gap;
+        //The header is centered on its position, and kept above the rows.
+        let end = rows.x + rows.columns.iter().map(|column| column.width).sum::<f64>();
+        let max_width = 2.0 * (extra.header_x - rows.x).min(end - extra.header_x);
+        let fit = TextFit { max_width, min_size: rows.header_size * 2.0 / 3.0, overflow: Overflow::Ellipsis };
+        let header = fill(&extra.header);
+        if generator.write_fitted(extra.header_x, extra_start - 1.0, rows.header_size, Center, &[(&header, Normal)], fit) {
+            cut.push(header);
+        }
+        for i in 0..extra.count {
+            draw_row(generator, extra_start + i as f64 * rows.distance, "_");
+        }
     }
+    cut
+}
 
-    let limit = match scorecard.limit(limits) {
-        TimeLimit::Single(z) => format!("{}: {}", language.time_limit, time_string(*z)),
-        TimeLimit::Cumulative(z) => format!("{}: {}", language.cumulative_limit, time_string(*z)),
-        TimeLimit::Cutoff(x, z) => format!("{}: {}, {}: {}", language.curoff, time_string(*x), language.time_limit, time_string(*z)),
-        TimeLimit::SharedCumulative(z, vec) => format!("{}: {} {} {}", language.cumulative_limit, time_string(*z), language.for_scl, vec.iter().map(|x|get_event(x)).collect::<Vec<_>>().join(&format!(" {} ", language.and_scl))),
-        TimeLimit::Multi => language.multi_tl.to_owned(),
-        TimeLimit::None => format!("")
+fn draw_qr_code<B: Backend>(generator: &mut ScorecardGenerator<B>, qr: &QrCodeElement, payload: &str) {
+    //Medium error correction still gives a small code for the default payload, while surviving a
+    //bit of pen on the card. Payloads too long for a QR code are left out.
+    let code = match QrCode::with_error_correction_level(payload, EcLevel::M) {
+        Ok(v) => v,
+        Err(_) => return
     };
+    let dark: Vec<bool> = code.to_colors().into_iter().map(|color| color == Color::Dark).collect();
+    generator.draw_qr_code(qr.x, qr.y, qr.size, code.width(), &dark);
+}
 
-    if generator.get_width_of_string(&limit, 7.0, Normal) <= 95.0 {
-        generator.write(&limit, 100.0, 94.0, 7.0, Right, Normal);
+fn draw_grid<B: Backend>(generator: &mut ScorecardGenerator<B>, grid: &GridElement) {
+    for row in 0..grid.rows {
+        let y = grid.y + row as f64 * grid.cell_height;
+        for column in 0..grid.columns {
+            generator.draw_square(grid.x + column as f64 * grid.cell_width, y, grid.cell_width, grid.cell_height);
+        }
+        if let Some(size) = grid.count_size {
+            let x = grid.x + grid.columns as f64 * grid.cell_width + 1.0;
+            generator.write(&((row + 1) * grid.columns).to_string(), x, y + grid.cell_height - 1.0, size, Left, Normal);
+        }
     }
-    let station_text = match scorecard.station() {
-        Some(v) => v.to_string(),
-        None => "".to_string()
-    };
-    generator.write(&station_text, 100.0, 12.0, 20.0, Right, Bold);
+}
+
+/// The multi-blind time limit in centiseconds for the number of cubes attempted: 10 minutes per
+/// cube, but at most an hour.
+pub(crate) fn multi_blind_limit(cubes: usize) -> usize {
+    cubes.min(6) * 60000
+}
+
+/// The rule for the multi-blind time limit, as the number of cubes is only known at the attempt.
+fn multi_blind_text(language: &Language) -> String {
+    //The limits are whole minutes, so they are written without seconds.
+    let minutes = |limit: usize| format!("{}:00", limit / 6000);
+    let rule = language.text("multi_tl")
+        .replace("{per_cube}", &minutes(multi_blind_limit(1)))
+        .replace("{max}", &minutes(multi_blind_limit(usize::MAX)));
+    format!("{}: {rule}", language.text("time_limit"))
+}
+
+/// The label of a cutoff made in the given number of attempts. Rounds without a known format have
+/// the usual cutoff after two attempts.
+fn cutoff_label(language: &Language, attempts: Option<usize>) -> String {
+    match attempts.unwrap_or(2) {
+        1 => language.text("cutoff_one").to_string(),
+        attempts => language.text("cutoff").replace("{attempts}", &attempts.to_string())
+    }
+}
+
+/// Replaces every `{key}` in the text. Unknown keys are left as they are.
+pub(crate) fn fill_placeholders(text: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            None => break,
+            Some(v) => start + v
+        };
+        result.push_str(&rest[..start]);
+        match value(&rest[start + 1..end]) {
+            Some(v) => result.push_str(&v),
+            None => result.push_str(&rest[start..=end])
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
 }
 
 fn time_string(mut z: usize) -> String {
@@ -89,23 +227,49 @@ fn time_string(mut z: usize) -> String {
 fn get_event_func<'a>(language: &'a Language) -> impl Fn(&str) -> &'a str {
     |x| match x {
         "" => "___________________________",
-        "333" => &language.e333,
-        "444" => &language.e444,
-        "555" => &language.e555,
-        "666" => &language.e666,
-        "777" => &language.e777,
-        "222" => &language.e222,
-        "333oh" => &language.e333oh,
-        "333fm" => "Filter out FMC",
-        "333bf" => &language.e333bf,
-        "pyram" => &language.epyram,
-        "333mbf" => &language.e333mbf,
-        "minx" => &language.eminx,
-        "clock" => &language.eclock,
-        "444bf" => &language.e444bf,
-        "555bf" => &language.e555bf,
-        "skewb" => &language.eskewb,
-        "sq1" => &language.esq1,
-        _ => "Please fix your csv"
+        x => language.event(x).unwrap_or("Please fix your csv")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use crate::language::Language;
+    use super::{cutoff_label, fill_placeholders, multi_blind_limit, multi_blind_text};
+
+    #[test]
+    fn cutoff_labels() {
+        let english = Language::english();
+        assert_eq!(cutoff_label(&english, Some(1)), "One attempt to get below");
+        assert_eq!(cutoff_label(&english, Some(3)), "3 attempts to get below");
+        assert_eq!(cutoff_label(&english, None), "2 attempts to get below");
+        let german = Language::builtin("de").unwrap();
+        assert_eq!(cutoff_label(&german, Some(2)), "2 Versuche, um zu unterbieten");
+    }
+
+    #[test]
+    fn multi_blind_limits() {
+        assert_eq!(multi_blind_limit(1), 60000);
+        assert_eq!(multi_blind_limit(4), 240000);
+        assert_eq!(multi_blind_limit(6), 360000);
+        assert_eq!(multi_blind_limit(12), 360000);
+        assert_eq!(multi_blind_text(&Language::english()), "Time limit: 10:00 per cube up to 60:00");
+        assert_eq!(multi_blind_text(&Language::builtin("de").unwrap()), "Zeitlimit: 10:00 pro Würfel, höchstens 60:00");
+    }
+
+    #[test]
+    fn placeholders() {
+        let value = |key: &str| match key {
+            "name" => Some("Ana".to_string()),
+            "id" => Some("12".to_string()),
+            "empty" => Some(String::new()),
+            _ => None
+        };
+        assert_eq!(fill_placeholders("{id}: {name}", value), "12: Ana");
+        assert_eq!(fill_placeholders("{name}{name}", value), "AnaAna");
+        assert_eq!(fill_placeholders("[{empty}]", value), "[]");
+        assert_eq!(fill_placeholders("{unknown} {name}", value), "{unknown} Ana");
+        assert_eq!(fill_placeholders("{name} {unclosed", value), "Ana {unclosed");
+        assert_eq!(fill_placeholders("{a{name}", value), "{a{name}");
+        assert_eq!(fill_placeholders("no placeholders", value), "no placeholders");
+    }
+}
@@ -1,6 +1,15 @@
+use font_kit::family_name::FamilyName;
 use font_kit::loaders::default::Font;
-use printpdf::{IndirectFontRef, PdfDocumentReference};
-use std::sync::Arc;
+use font_kit::properties::{Properties, Weight as FontWeight};
+use font_kit::source::SystemSource;
+use printpdf::IndirectFontRef;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::cell::OnceCell;
+use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
+use crate::backend::PT;
+use crate::scorecard_generator::Weight;
 
 pub type FontWidth = Font;
 pub type FontPDF = IndirectFontRef;
@@ -8,14 +17,373 @@ pub type FontPDF = IndirectFontRef;
 const NORMAL: &[u8] = include_bytes!("../dependencies/Helvetica.ttf");
 const BOLD: &[u8] = include_bytes!("../dependencies/Helvetica-Bold.ttf");
 
-pub fn load_fonts(doc: &PdfDocumentReference, weight: &str) -> (FontWidth, FontPDF) {
-    let bytes = match weight {
-        "normal" => NORMAL,
-        "bold" => BOLD,
-        _ => NORMAL
+/// The bundled font of the weight, which every character falls back to.
+pub(crate) fn bundled(weight: Weight) -> &'static [u8] {
+    match weight {
+        Weight::Normal => NORMAL,
+        Weight::Bold => BOLD,
+    }
+}
+
+/// Printpdf only embeds single TrueType fonts, not font collections or fonts with CFF outlines.
+fn embeddable(bytes: &[u8]) -> bool {
+    matches!(bytes.get(..4), Some([0, 1, 0, 0] | b"true"))
+}
+
+/// System fonts tried for characters which neither Helvetica nor the user fonts have.
+const FALLBACK_FAMILIES: [&str; 14] = [
+    "Noto Sans",
+    "DejaVu Sans",
+    "Arial",
+    "Arial Unicode MS",
+    "Noto Sans CJK SC",
+    "Noto Sans CJK JP",
+    "Noto Sans CJK KR",
+    "PingFang SC",
+    "Microsoft YaHei",
+    "Malgun Gothic",
+    "Noto Sans Arabic",
+    "Noto Sans Hebrew",
+    "Noto Sans Devanagari",
+    "Noto Sans Thai",
+];
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    Unreadable(String),
+}
+
+impl Display for FontError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Io(e) => write!(f, "Unable to read font: {e}"),
+            FontError::Unreadable(e) => write!(f, "Not a usable font file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// The fonts used for text on scorecards. Every character is written in the first font which has
+/// it: the bundled Helvetica, then the added fonts in the order they were added and last a set of
+/// common system fonts. Fonts are only embedded in a pdf if some text on it uses them.
+#[derive(Clone)]
+pub struct Fonts {
+    normal: Vec<Arc<Vec<u8>>>,
+    bold: Vec<Arc<Vec<u8>>>,
+    system_fallback: bool,
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Fonts::new()
+    }
+}
+
+impl Fonts {
+    pub fn new() -> Fonts {
+        Fonts { normal: vec![], bold: vec![], system_fallback: true }
+    }
+
+    pub fn add_font(&mut self, bytes: Vec<u8>, weight: Weight) -> Result<(), FontError> {
+        if !embeddable(&bytes) {
+            return Err(FontError::Unreadable("only TrueType fonts can be embedded, not collections or fonts with CFF outlines".to_string()));
+        }
+        let bytes = Arc::new(bytes);
+        Font::from_bytes(bytes.clone(), 0).map_err(|e| FontError::Unreadable(e.to_string()))?;
+        match weight {
+            Weight::Normal => self.normal.push(bytes),
+            Weight::Bold => self.bold.push(bytes),
+        }
+        Ok(())
+    }
+
+    pub fn add_font_file(&mut self, path: impl AsRef<Path>, weight: Weight) -> Result<(), FontError> {
+        let bytes = std::fs::read(path).map_err(FontError::Io)?;
+        self.add_font(bytes, weight)
+    }
+
+    /// Whether to look for system fonts when no other font has a character. Turning it off gives the
+    /// same output on every machine.
+    pub fn set_system_fallback(&mut self, system_fallback: bool) {
+        self.system_fallback = system_fallback;
+    }
+
+    /// Parses the fonts, which can then be used for any number of documents.
+    pub(crate) fn load(&self) -> LoadedFonts {
+        LoadedFonts { normal: self.stack(Weight::Normal), bold: self.stack(Weight::Bold) }
+    }
+
+    fn stack(&self, weight: Weight) -> FontStack {
+        let added = match weight {
+            Weight::Normal => &self.normal,
+            Weight::Bold => &self.bold,
+        };
+        let faces = std::iter::once(Arc::new(bundled(weight).to_vec()))
+            .chain(added.iter().cloned())
+            .filter_map(Face::new)
+            .collect();
+        let families = match self.system_fallback {
+            true => FALLBACK_FAMILIES.len(),
+            false => 0
+        };
+        FontStack::new(faces, Fallback::new(weight, families, system_font))
+    }
+}
+
+/// The families in the order the pdf tries them, for viewers which pick the fonts themselves, such as
+/// browsers showing svg previews. Written for an svg or css `font-family`.
+pub(crate) fn font_families() -> &'static str {
+    static FAMILIES: OnceLock<String> = OnceLock::new();
+    FAMILIES.get_or_init(|| std::iter::once("Helvetica")
+        .chain(FALLBACK_FAMILIES)
+        .map(|family| format!("'{family}'"))
+        .chain(std::iter::once("sans-serif".to_string()))
+        .collect::<Vec<_>>()
+        .join(", "))
+}
+
+/// Looked up at most once per family, as searching the system fonts is slow and some of the
+/// families, like the CJK ones, are large.
+fn system_font(weight: Weight, family: usize) -> Option<Arc<Vec<u8>>> {
+    type Family = OnceLock<Option<Arc<Vec<u8>>>>;
+    static SYSTEM_FONTS: [[Family; FALLBACK_FAMILIES.len()]; 2] = [const { [const { OnceLock::new() }; FALLBACK_FAMILIES.len()] }; 2];
+    let (idx, font_weight) = match weight {
+        Weight::Normal => (0, FontWeight::NORMAL),
+        Weight::Bold => (1, FontWeight::BOLD),
     };
-    let font_width = Font::from_bytes(Arc::new(bytes.to_vec()), 0).unwrap();
-    let font = doc.add_external_font(bytes).unwrap();
+    SYSTEM_FONTS[idx][family].get_or_init(|| {
+        let name = FamilyName::Title(FALLBACK_FAMILIES[family].to_string());
+        let handle = SystemSource::new().select_best_match(&[name], Properties::new().weight(font_weight)).ok()?;
+        handle.load().ok()?.copy_font_data()
+    }).clone()
+}
+
+/// Both weights of the fonts, parsed once. Cloning is cheap, so every document gets its own.
+#[derive(Clone)]
+pub(crate) struct LoadedFonts {
+    normal: FontStack,
+    bold: FontStack,
+}
+
+impl LoadedFonts {
+    pub fn stack(&self, weight: Weight) -> &FontStack {
+        match weight {
+            Weight::Normal => &self.normal,
+            Weight::Bold => &self.bold,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Face {
+    width: Rc<FontWidth>,
+    bytes: Arc<Vec<u8>>,
+}
+
+impl Face {
+    /// System fonts printpdf can not embed are skipped, so their characters are looked for in the
+    /// next family.
+    fn new(bytes: Arc<Vec<u8>>) -> Option<Face> {
+        if !embeddable(&bytes) {
+            return None;
+        }
+        Some(Face { width: Rc::new(Font::from_bytes(bytes.clone(), 0).ok()?), bytes })
+    }
+
+    fn has(&self, char: char) -> bool {
+        self.width.glyph_for_char(char).is_some()
+    }
+}
+
+/// The system fonts of a stack, one slot per fallback family. A family is only loaded when a
+/// character is missing from every face before it. Shared by the clones of the stack.
+struct Fallback {
+    weight: Weight,
+    faces: Vec<OnceCell<Option<Face>>>,
+    load: fn(Weight, usize) -> Option<Arc<Vec<u8>>>,
+}
+
+impl Fallback {
+    fn new(weight: Weight, families: usize, load: fn(Weight, usize) -> Option<Arc<Vec<u8>>>) -> Fallback {
+        Fallback { weight, faces: (0..families).map(|_| OnceCell::new()).collect(), load }
+    }
+}
+
+/// Faces are indexed with the bundled and added fonts first, followed by the fallback families.
+#[derive(Clone)]
+pub(crate) struct FontStack {
+    faces: Vec<Face>,
+    fallback: Rc<Fallback>,
+}
+
+impl FontStack {
+    fn new(faces: Vec<Face>, fallback: Fallback) -> FontStack {
+        FontStack { faces, fallback: Rc::new(fallback) }
+    }
+
+    fn face_for(&self, char: char) -> usize {
+        self.faces.iter()
+            .position(|face| face.has(char))
+            .or_else(|| (0..self.fallback.faces.len())
+                .find(|family| self.fallback_face(*family).is_some_and(|face| face.has(char)))
+                .map(|family| self.faces.len() + family))
+            .unwrap_or(0)
+    }
+
+    fn fallback_face(&self, family: usize) -> Option<&Face> {
+        self.fallback.faces[family]
+            .get_or_init(|| (self.fallback.load)(self.fallback.weight, family).and_then(Face::new))
+            .as_ref()
+    }
+
+    /// Only indices returned by `runs` are valid, so fallback faces are always loaded already.
+    fn face(&self, idx: usize) -> &Face {
+        match idx.checked_sub(self.faces.len()) {
+            None => &self.faces[idx],
+            Some(family) => self.fallback_face(family).expect("Face index not returned by runs"),
+        }
+    }
+
+    /// Splits the text into runs which are written with the same font, given by its index.
+    /// Whitespace stays in the run it is in, so words are only split where the font changes.
+    pub fn runs<'t>(&self, text: &'t str) -> Vec<(usize, &'t str)> {
+        let mut runs = vec![];
+        let mut start = 0;
+        let mut current = None;
+        for (idx, char) in text.char_indices() {
+            if char.is_whitespace() {
+                continue;
+            }
+            let face = self.face_for(char);
+            match current {
+                Some(c) if c != face => {
+                    runs.push((c, &text[start..idx]));
+                    start = idx;
+                }
+                _ => ()
+            }
+            current = Some(face);
+        }
+        if start < text.len() {
+            runs.push((current.unwrap_or(0), &text[start..]));
+        }
+        runs
+    }
+
+    /// Width in mm, measured in the same runs as the text is written in.
+    pub fn width(&self, text: &str, font_size: f64) -> f64 {
+        self.runs(text).into_iter()
+            .map(|(face, run)| get_width_of_string(&self.face(face).width, run, font_size))
+            .sum()
+    }
+
+    /// Every font file of the stack, in the order they are tried. This loads all fallback
+    /// families, as it is not known which characters the files will be used for.
+    #[cfg(feature = "png")]
+    pub fn files(&self) -> impl Iterator<Item = &[u8]> {
+        let fallback = (0..self.fallback.faces.len()).filter_map(|family| self.fallback_face(family));
+        self.faces.iter().chain(fallback).map(|face| face.bytes.as_slice())
+    }
+
+    /// The font file of the face with the given index.
+    pub fn bytes(&self, face: usize) -> &[u8] {
+        &self.face(face).bytes
+    }
+}
+
+pub fn get_width_of_string(font: &Font, string: &str, font_size: f64) -> f64 {
+    let upem = font.metrics().units_per_em;
+    let mut width = 0.0;
+    for char in string.chars() {
+        if !char.is_whitespace() {
+            if let Some(id) = font.glyph_for_char(char) {
+                let glyph_width = font.advance(id).unwrap().x();
+                width += glyph_width
+            }
+        } else {
+            width += upem as f32 / 4.0;
+        }
+    }
+    width as f64 / upem as f64 * font_size * PT
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use crate::backend::PT;
+    use crate::scorecard_generator::Weight;
+    use super::{Face, Fallback, FontStack, Fonts};
+
+    /// Square glyphs, 1000 units wide, for 中 and 文 and nothing else.
+    const FALLBACK: &[u8] = include_bytes!("../tests/fixtures/Fallback.ttf");
+
+    fn fonts() -> Fonts {
+        let mut fonts = Fonts::new();
+        fonts.set_system_fallback(false);
+        fonts.add_font(FALLBACK.to_vec(), Weight::Normal).unwrap();
+        fonts
+    }
+
+    #[test]
+    fn runs() {
+        let stack = fonts().stack(Weight::Normal);
+        assert_eq!(stack.runs("Ana Smith"), vec![(0, "Ana Smith")]);
+        assert_eq!(stack.runs(""), vec![]);
+        //Whitespace stays in the run before it.
+        assert_eq!(stack.runs("Ana 中文"), vec![(0, "Ana "), (1, "中文")]);
+        assert_eq!(stack.runs("中a文"), vec![(1, "中"), (0, "a"), (1, "文")]);
+        //Characters no font has are written in the first one.
+        assert_eq!(stack.runs("Ana 日"), vec![(0, "Ana 日")]);
+        //Added fonts are only used for the weight they were added for.
+        assert_eq!(fonts().stack(Weight::Bold).runs("中"), vec![(0, "中")]);
+    }
+
+    #[test]
+    fn width_of_runs() {
+        let stack = fonts().stack(Weight::Normal);
+        assert!((stack.width("中文", 10.0) - 2.0 * 10.0 * PT).abs() < 1e-9);
+        assert!((stack.width("Ana 中", 10.0) - stack.width("Ana ", 10.0) - 10.0 * PT).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unreadable_fonts() {
+        assert!(Fonts::new().add_font(vec![0; 16], Weight::Normal).is_err());
+        assert!(Fonts::new().add_font_file("missing.ttf", Weight::Normal).is_err());
+        //Printpdf can not embed fonts with CFF outlines or font collections.
+        for header in [b"OTTO", b"ttcf"] {
+            let mut font = FALLBACK.to_vec();
+            font[..4].copy_from_slice(header);
+            assert!(Fonts::new().add_font(font.clone(), Weight::Normal).is_err());
+            assert!(Face::new(Arc::new(font)).is_none());
+        }
+    }
+
+    static TRIED: Mutex<Vec<usize>> = Mutex::new(vec![]);
+
+    //Only the third family is installed.
+    fn load(_: Weight, family: usize) -> Option<Arc<Vec<u8>>> {
+        TRIED.lock().unwrap().push(family);
+        (family == 2).then(|| Arc::new(FALLBACK.to_vec()))
+    }
 
-    (font_width, font)
+    #[test]
+    fn lazy_fallback() {
+        let faces = Fonts::new().stack(Weight::Normal).faces;
+        let stack = FontStack::new(faces, Fallback::new(Weight::Normal, 4, load));
+        assert_eq!(stack.runs("Ana Smith"), vec![(0, "Ana Smith")]);
+        assert!(TRIED.lock().unwrap().is_empty());
+        //Families are tried in order until one has the character.
+        assert_eq!(stack.runs("Ana 中"), vec![(0, "Ana "), (3, "中")]);
+        assert_eq!(*TRIED.lock().unwrap(), vec![0, 1, 2]);
+        //Clones share the loaded faces, so nothing is loaded twice.
+        let clone = stack.clone();
+        assert_eq!(clone.runs("文"), vec![(3, "文")]);
+        assert!((clone.width("中", 10.0) - 10.0 * PT).abs() < 1e-9);
+        assert_eq!(*TRIED.lock().unwrap(), vec![0, 1, 2]);
+        //Characters no family has load the remaining ones.
+        assert_eq!(clone.runs("日"), vec![(0, "日")]);
+        assert_eq!(*TRIED.lock().unwrap(), vec![0, 1, 2, 3]);
+    }
 }
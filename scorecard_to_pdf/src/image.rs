@@ -0,0 +1,266 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::Arc;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use jpeg_decoder::PixelFormat;
+use printpdf::{ImageXObject, Px, ColorSpace, ColorBits, ImageFilter};
+
+/// Pixels on the longest side of rendered svg images, enough for a logo of a few cm.
+#[cfg(feature = "svg")]
+const SVG_RESOLUTION: f32 = 600.0;
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(std::io::Error),
+    /// Neither png, jpeg nor svg.
+    UnknownFormat,
+    Unreadable(String),
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "Unable to read image: {e}"),
+            ImageError::UnknownFormat => write!(f, "Images must be png, jpeg or svg"),
+            ImageError::Unreadable(e) => write!(f, "Not a usable image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// A png, jpeg or svg image, such as the logo of the competition. It is read once when it is made,
+/// and shared by every scorecard it is drawn on. Svg images need the `svg` feature, and are drawn
+/// as pixels in pdfs.
+#[derive(Clone)]
+pub struct Image {
+    inner: Arc<ImageData>,
+}
+
+struct ImageData {
+    /// The file as given, for backends which can embed it as it is.
+    bytes: Vec<u8>,
+    mime: &'static str,
+    width: usize,
+    height: usize,
+    pixels: Pixels,
+}
+
+enum Pixels {
+    /// Jpeg files are embedded in pdfs without decoding them.
+    Jpeg { grey: bool },
+    /// Rgb pixels, with transparent parts on white.
+    Rgb(Vec<u8>),
+}
+
+impl Image {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Image, ImageError> {
+        let (mime, width, height, pixels) = if bytes.starts_with(b"\x89PNG") {
+            let (width, height, rgb) = decode_png(&bytes)?;
+            ("image/png", width, height, Pixels::Rgb(rgb))
+        }
+        else if bytes.starts_with(&[0xFF, 0xD8]) {
+            let (width, height, grey) = jpeg_info(&bytes)?;
+            ("image/jpeg", width, height, Pixels::Jpeg { grey })
+        }
+        else if is_svg(&bytes) {
+            let (width, height, rgb) = render_svg(&bytes)?;
+            ("image/svg+xml", width, height, Pixels::Rgb(rgb))
+        }
+        else {
+            return Err(ImageError::UnknownFormat);
+        };
+        Ok(Image { inner: Arc::new(ImageData { bytes, mime, width, height, pixels }) })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Image, ImageError> {
+        let bytes = std::fs::read(path).map_err(ImageError::Io)?;
+        Image::from_bytes(bytes)
+    }
+
+    /// Width divided by height.
+    pub fn aspect_ratio(&self) -> f64 {
+        self.inner.width as f64 / self.inner.height as f64
+    }
+
+    /// Whether both are the same image, and not just equal ones.
+    pub(crate) fn same(&self, other: &Image) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    pub(crate) fn xobject(&self) -> ImageXObject {
+        let data = &self.inner;
+        let (color_space, image_data, image_filter) = match &data.pixels {
+            Pixels::Jpeg { grey } => {
+                let color_space = if *grey { ColorSpace::Greyscale } else { ColorSpace::Rgb };
+                (color_space, data.bytes.clone(), Some(ImageFilter::DCT))
+            }
+            Pixels::Rgb(rgb) => (ColorSpace::Rgb, rgb.clone(), None),
+        };
+        ImageXObject {
+            width: Px(data.width),
+            height: Px(data.height),
+            color_space,
+            bits_per_component: ColorBits::Bit8,
+            interpolate: true,
+            image_data,
+            image_filter,
+            clipping_bbox: None,
+        }
+    }
+
+    /// The file as a data url, to use in svg and html.
+    pub(crate) fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.inner.mime, BASE64.encode(&self.inner.bytes))
+    }
+}
+
+impl std::fmt::Debug for Image {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Image({}, {}x{})", self.inner.mime, self.inner.width, self.inner.height)
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>), ImageError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| ImageError::Unreadable(e.to_string()))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| ImageError::Unreadable(e.to_string()))?;
+    let pixels = &buffer[..info.buffer_size()];
+    let rgb = match info.color_type {
+        png::ColorType::Rgb => pixels.to_vec(),
+        png::ColorType::Rgba => pixels.chunks_exact(4).flat_map(|p| on_white([p[0], p[1], p[2]], p[3])).collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|g| [*g; 3]).collect(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|p| on_white([p[0]; 3], p[1])).collect(),
+        png::ColorType::Indexed => return Err(ImageError::Unreadable("Unexpanded palette".to_string())),
+    };
+    Ok((info.width as usize, info.height as usize, rgb))
+}
+
+/// Pdf images have no transparency here, so transparent pixels are drawn on the white card.
+fn on_white(rgb: [u8; 3], alpha: u8) -> [u8; 3] {
+    rgb.map(|c| ((c as u32 * alpha as u32 + 255 * (255 - alpha as u32)) / 255) as u8)
+}
+
+/// Size and colours of a jpeg file. Pdfs embed the file as it is, so it is decoded once to make
+/// sure it is not broken.
+fn jpeg_info(bytes: &[u8]) -> Result<(usize, usize, bool), ImageError> {
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+    decoder.decode().map_err(|e| ImageError::Unreadable(e.to_string()))?;
+    let info = decoder.info().ok_or_else(|| ImageError::Unreadable("Broken jpeg file".to_string()))?;
+    let grey = match info.pixel_format {
+        PixelFormat::L8 => true,
+        PixelFormat::RGB24 => false,
+        _ => return Err(ImageError::Unreadable("Only 8 bit greyscale and rgb jpeg files are supported".to_string())),
+    };
+    Ok((info.width as usize, info.height as usize, grey))
+}
+
+fn is_svg(bytes: &[u8]) -> bool {
+    let start = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_lowercase();
+    start.contains("<svg")
+}
+
+#[cfg(feature = "svg")]
+fn render_svg(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>), ImageError> {
+    use resvg::{tiny_skia, usvg};
+    let tree = usvg::Tree::from_data(bytes, &usvg::Options::default()).map_err(|e| ImageError::Unreadable(e.to_string()))?;
+    let scale = SVG_RESOLUTION / tree.size().width().max(tree.size().height());
+    let size = tree.size().to_int_size().scale_by(scale).ok_or_else(|| ImageError::Unreadable("Empty svg".to_string()))?;
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height()).ok_or_else(|| ImageError::Unreadable("Empty svg".to_string()))?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    //Pixels of tiny-skia have their colour multiplied by the alpha already.
+    let rgb = pixmap.data().chunks_exact(4).flat_map(|p| [0, 1, 2].map(|c| p[c].saturating_add(255 - p[3]))).collect();
+    Ok((size.width() as usize, size.height() as usize, rgb))
+}
+
+#[cfg(not(feature = "svg"))]
+fn render_svg(_: &[u8]) -> Result<(usize, usize, Vec<u8>), ImageError> {
+    Err(ImageError::Unreadable("Svg images need the svg feature".to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_svg, Image, ImageError, Pixels};
+
+    const RGB_JPEG: &[u8] = include_bytes!("../tests/fixtures/rgb.jpg");
+    const GREY_JPEG: &[u8] = include_bytes!("../tests/fixtures/grey.jpg");
+    /// An opaque red pixel next to a transparent one.
+    const TRANSPARENT_PNG: &[u8] = include_bytes!("../tests/fixtures/transparent.png");
+    const SVG: &[u8] = include_bytes!("../tests/fixtures/logo.svg");
+
+    fn size(image: &Image) -> (usize, usize) {
+        (image.inner.width, image.inner.height)
+    }
+
+    fn unreadable(bytes: &[u8]) -> bool {
+        matches!(Image::from_bytes(bytes.to_vec()), Err(ImageError::Unreadable(_)))
+    }
+
+    #[test]
+    fn png_on_white() {
+        let image = Image::from_bytes(TRANSPARENT_PNG.to_vec()).unwrap();
+        assert_eq!(size(&image), (2, 1));
+        assert!(matches!(&image.inner.pixels, Pixels::Rgb(rgb) if rgb == &[255, 0, 0, 255, 255, 255]));
+        assert!(image.data_url().starts_with("data:image/png;base64,iVBORw0KGgo"));
+    }
+
+    #[test]
+    fn jpeg_headers() {
+        let image = Image::from_bytes(RGB_JPEG.to_vec()).unwrap();
+        assert_eq!(size(&image), (4, 2));
+        assert_eq!(image.aspect_ratio(), 2.0);
+        assert!(matches!(image.inner.pixels, Pixels::Jpeg { grey: false }));
+        let image = Image::from_bytes(GREY_JPEG.to_vec()).unwrap();
+        assert_eq!(size(&image), (3, 5));
+        assert!(matches!(image.inner.pixels, Pixels::Jpeg { grey: true }));
+        assert!(image.data_url().starts_with("data:image/jpeg;base64,/9j/"));
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(unreadable(&TRANSPARENT_PNG[..30]));
+        assert!(unreadable(&RGB_JPEG[..20]));
+        //Jpeg files are embedded as they are, so broken image data has to be found before that.
+        assert!(unreadable(&RGB_JPEG[..RGB_JPEG.len() - 2]));
+        let scan = RGB_JPEG.windows(2).position(|w| w == [0xFF, 0xDA]).unwrap();
+        let mut corrupt = RGB_JPEG.to_vec();
+        corrupt[scan + 16..scan + 40].fill(0xFF);
+        assert!(unreadable(&corrupt));
+        assert!(unreadable(&[0xFF, 0xD8, 0x00, 0x00, 0x00]));
+        assert!(unreadable(&[0xFF, 0xD8, 0xFF, 0xE0, 0xFF, 0xFF]));
+        let mut corrupt = RGB_JPEG.to_vec();
+        let frame = corrupt.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        //Four components, which is neither greyscale nor rgb.
+        corrupt[frame + 9] = 4;
+        assert!(unreadable(&corrupt));
+    }
+
+    #[test]
+    fn sniffing() {
+        assert!(matches!(Image::from_bytes(b"GIF89a".to_vec()), Err(ImageError::UnknownFormat)));
+        assert!(matches!(Image::from_bytes(vec![]), Err(ImageError::UnknownFormat)));
+        assert!(is_svg(SVG));
+        assert!(is_svg(b"<SVG xmlns=\"http://www.w3.org/2000/svg\"/>"));
+        assert!(!is_svg(b"<html></html>"));
+        let mut late = vec![b' '; 1024];
+        late.extend(b"<svg/>");
+        assert!(!is_svg(&late));
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn svg_rendered() {
+        let image = Image::from_bytes(SVG.to_vec()).unwrap();
+        assert_eq!(size(&image), (600, 300));
+        assert!(matches!(&image.inner.pixels, Pixels::Rgb(rgb) if rgb[..3] == [204, 0, 0]));
+    }
+
+    #[cfg(not(feature = "svg"))]
+    #[test]
+    fn svg_needs_feature() {
+        assert!(unreadable(SVG));
+    }
+}
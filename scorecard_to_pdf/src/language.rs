@@ -1,67 +1,309 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use serde::Deserialize;
+
+/// Labels printed on scorecards, with their english text.
+const LABELS: [(&str, &str); 38] = [
+    ("round", "Round"),
+    ("group", "Group"),
+    ("scram", "scr"),
+    ("result", "result"),
+    ("judge", "judge"),
+    ("comp", "comp"),
+    ("extra_attempts", "Extra attempts"),
+    ("time_limit", "Time limit"),
+    ("cumulative_limit", "Cumulative limit"),
+    ("for_scl", "for"),
+    ("and_scl", "and"),
+    ("cutoff", "{attempts} attempts to get below"),
+    ("cutoff_one", "One attempt to get below"),
+    ("multi_tl", "{per_cube} per cube up to {max}"),
+    ("solution", "Solution"),
+    ("attempt", "Attempt"),
+    ("moves", "moves"),
+    ("signature", "Competitor signature"),
+    ("attempted", "attempted"),
+    ("solved", "solved"),
+    ("time", "time"),
+    ("competitor", "Competitor"),
+    ("delegate", "Delegate"),
+    ("trainee_delegate", "Trainee Delegate"),
+    ("organizer", "Organizer"),
+    ("staff", "Staff"),
+    ("scrambler", "scrambler"),
+    ("runner", "runner"),
+    ("data_entry", "data entry"),
+    ("announcer", "announcer"),
+    ("station", "Station"),
+    ("name", "Name"),
+    ("competitor_id", "ID"),
+    ("task", "Task"),
+    ("by_name", "By name"),
+    ("schedule_time", "Time"),
+    ("event", "Event"),
+    ("room", "Room"),
+];
+
+/// Old names of labels, still accepted in translations.
+const ALIASES: [(&str, &str); 1] = [
+    ("curoff", "cutoff"),
+];
+
+/// The current key of a label which may be given by an old name.
+fn resolve_alias(key: &str) -> &str {
+    ALIASES.iter().find(|(alias, _)| *alias == key).map_or(key, |(_, key)| key)
+}
+
+/// Names of the events, keyed by their WCA event id.
+const EVENTS: [(&str, &str); 17] = [
+    ("333", "3x3x3 Cube"),
+    ("222", "2x2x2 Cube"),
+    ("444", "4x4x4 Cube"),
+    ("555", "5x5x5 Cube"),
+    ("666", "6x6x6 Cube"),
+    ("777", "7x7x7 Cube"),
+    ("333bf", "3x3x3 Blindfolded"),
+    ("333fm", "3x3x3 Fewest Moves"),
+    ("333oh", "3x3x3 One Handed"),
+    ("clock", "Clock"),
+    ("minx", "Megaminx"),
+    ("pyram", "Pyraminx"),
+    ("skewb", "Skewb"),
+    ("sq1", "Square 1"),
+    ("444bf", "4x4x4 Blindfolded"),
+    ("555bf", "5x5x5 Blindfolded"),
+    ("333mbf", "3x3x3 Multi-Blind"),
+];
+
+/// Translations shipped with the crate, keyed by language code.
+const BUILTIN: [(&str, &str); 8] = [
+    ("ar", include_str!("../locales/ar.toml")),
+    ("da", include_str!("../locales/da.toml")),
+    ("de", include_str!("../locales/de.toml")),
+    ("es", include_str!("../locales/es.toml")),
+    ("fr", include_str!("../locales/fr.toml")),
+    ("it", include_str!("../locales/it.toml")),
+    ("nl", include_str!("../locales/nl.toml")),
+    ("pt", include_str!("../locales/pt.toml")),
+];
+
+#[derive(Debug)]
+pub enum LanguageError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    UnknownFormat(String),
+    UnknownLabel(String),
+    UnknownEvent(String),
+}
+
+impl Display for LanguageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LanguageError::Io(e) => write!(f, "Unable to read translation: {e}"),
+            LanguageError::Json(e) => write!(f, "Malformed json translation: {e}"),
+            LanguageError::Toml(e) => write!(f, "Malformed toml translation: {e}"),
+            LanguageError::UnknownFormat(ext) => write!(f, "Translations must be .json or .toml files, not \"{ext}\""),
+            LanguageError::UnknownLabel(key) => write!(f, "Translation for unknown label \"{key}\""),
+            LanguageError::UnknownEvent(id) => write!(f, "Translation for unknown event id \"{id}\""),
+        }
+    }
+}
+
+impl std::error::Error for LanguageError {}
+
+/// The format of translation files. In toml:
+///
+/// ```toml
+/// right_to_left = false
+///
+/// [labels]
+/// round = "Runde"
+/// group = "Gruppe"
+///
+/// [events]
+/// 333 = "3x3x3 Terning"
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TranslationFile {
+    #[serde(default)]
+    right_to_left: bool,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    #[serde(default)]
+    events: HashMap<String, String>,
+}
+
+/// Text printed on scorecards. Labels and event names which are not translated are written in
+/// english.
+#[derive(Clone, Debug)]
 pub struct Language {
-    pub round: String,
-    pub group: String,
-    pub scram: String,
-    pub result: String,
-    pub judge: String,
-    pub comp: String,
-    pub extra_attempts: String,
-    pub time_limit: String,
-    pub cumulative_limit: String,
-    pub for_scl: String,
-    pub and_scl: String,
-    pub curoff: String,
-    pub multi_tl: String,
-    pub e333: String,
-    pub e444: String,
-    pub e555: String,
-    pub e666: String,
-    pub e777: String,
-    pub e222: String,
-    pub e333oh: String,
-    pub eclock: String,
-    pub eminx: String,
-    pub epyram: String,
-    pub e333bf: String,
-    pub e444bf: String,
-    pub e555bf: String,
-    pub e333mbf: String,
-    pub esq1: String,
-    pub eskewb: String
+    labels: HashMap<String, String>,
+    events: HashMap<String, String>,
+    /// Set for languages written from right to left, such as Arabic, Hebrew and Persian. The whole
+    /// scorecard is mirrored, so it reads from the right.
+    pub right_to_left: bool,
 }
 
 impl Language {
     pub fn english() -> Self {
-        Language { 
-            round: format!("Round"), 
-            group: format!("Group"), 
-            scram: format!("scr"), 
-            result: format!("result"), 
-            judge: format!("judge"), 
-            comp: format!("comp"), 
-            extra_attempts: format!("Extra attempts"),
-            time_limit: format!("Time limit"), 
-            cumulative_limit: format!("Cumulative limit"), 
-            for_scl: format!("for"), 
-            and_scl: format!("and"), 
-            curoff: format!("Two attempts to get below"), 
-            multi_tl: format!("10:00 per cube up to 60:00"), 
-            e333: format!("3x3x3 Cube"), 
-            e444: format!("4x4x4 Cube"), 
-            e555: format!("5x5x5 Cube"), 
-            e666: format!("6x6x6 Cube"), 
-            e777: format!("7x7x7 Cube"), 
-            e222: format!("2x2x2 Cube"), 
-            e333oh: format!("3x3x3 One Handed"), 
-            eclock: format!("Clock"), 
-            eminx: format!("Megaminx"), 
-            epyram: format!("Pyraminx"), 
-            e333bf: format!("3x3x3 Blindfolded"), 
-            e444bf: format!("4x4x4 Blindfolded"), 
-            e555bf: format!("5x5x5 Blindfolded"), 
-            e333mbf: format!("3x3x3 Multi-Blind"), 
-            esq1: format!("Square 1"), 
-            eskewb: format!("Skewb")
+        let to_map = |pairs: &[(&str, &str)]| pairs.iter().map(|(key, text)| (key.to_string(), text.to_string())).collect();
+        Language {
+            labels: to_map(&LABELS),
+            events: to_map(&EVENTS),
+            right_to_left: false,
+        }
+    }
+
+    /// One of the translations shipped with the crate, by language code, e.g. `"da"`.
+    pub fn builtin(code: &str) -> Option<Language> {
+        if code == "en" {
+            return Some(Language::english());
+        }
+        let (_, toml) = BUILTIN.iter().find(|(c, _)| *c == code)?;
+        Some(Language::from_toml(toml).expect("Bundled translations are valid"))
+    }
+
+    /// Codes of the translations accepted by `Language::builtin`.
+    pub fn builtin_codes() -> impl Iterator<Item = &'static str> {
+        std::iter::once("en").chain(BUILTIN.iter().map(|(code, _)| *code))
+    }
+
+    pub fn from_json(json: &str) -> Result<Language, LanguageError> {
+        let file = serde_json::from_str(json).map_err(LanguageError::Json)?;
+        Language::from_file(file)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Language, LanguageError> {
+        let file = toml::from_str(toml).map_err(LanguageError::Toml)?;
+        Language::from_file(file)
+    }
+
+    /// Loads a translation from a .json or .toml file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Language, LanguageError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(LanguageError::Io)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Language::from_json(&content),
+            Some("toml") => Language::from_toml(&content),
+            ext => Err(LanguageError::UnknownFormat(ext.unwrap_or("").to_string()))
+        }
+    }
+
+    fn from_file(mut file: TranslationFile) -> Result<Language, LanguageError> {
+        if let Some(key) = file.labels.keys().find(|key| !LABELS.iter().any(|(k, _)| *k == resolve_alias(key))) {
+            return Err(LanguageError::UnknownLabel(key.clone()));
         }
+        for (alias, key) in ALIASES {
+            if let Some(text) = file.labels.remove(alias) {
+                file.labels.entry(key.to_string()).or_insert(text);
+            }
+        }
+        if let Some(id) = file.events.keys().find(|id| !EVENTS.iter().any(|(k, _)| k == id)) {
+            return Err(LanguageError::UnknownEvent(id.clone()));
+        }
+        Ok(Language { labels: file.labels, events: file.events, right_to_left: file.right_to_left })
+    }
+
+    /// Keys which have no translation and are written in english, as `labels.<key>` and
+    /// `events.<id>`. Empty for a complete translation.
+    pub fn missing_keys(&self) -> Vec<String> {
+        let labels = LABELS.iter()
+            .filter(|(key, _)| !self.labels.contains_key(*key))
+            .map(|(key, _)| format!("labels.{key}"));
+        let events = EVENTS.iter()
+            .filter(|(id, _)| !self.events.contains_key(*id))
+            .map(|(id, _)| format!("events.{id}"));
+        labels.chain(events).collect()
+    }
+
+    /// Looks up a label by its key, as used by `{lang.<key>}` in scorecard templates.
+    pub fn label(&self, key: &str) -> Option<&str> {
+        let key = resolve_alias(key);
+        match self.labels.get(key) {
+            Some(text) => Some(text),
+            None => LABELS.iter().find(|(k, _)| *k == key).map(|(_, text)| *text)
+        }
+    }
+
+    /// The name of an event by its WCA event id.
+    pub fn event(&self, id: &str) -> Option<&str> {
+        match self.events.get(id) {
+            Some(text) => Some(text),
+            None => EVENTS.iter().find(|(k, _)| *k == id).map(|(_, text)| *text)
+        }
+    }
+
+    /// Overrides a single label. Fails for keys which are not printed anywhere.
+    pub fn set_label(&mut self, key: &str, text: impl Into<String>) -> Result<(), LanguageError> {
+        let key = resolve_alias(key);
+        if !LABELS.iter().any(|(k, _)| *k == key) {
+            return Err(LanguageError::UnknownLabel(key.to_string()));
+        }
+        self.labels.insert(key.to_string(), text.into());
+        Ok(())
+    }
+
+    /// Overrides the name of a single event.
+    pub fn set_event(&mut self, id: &str, text: impl Into<String>) -> Result<(), LanguageError> {
+        if !EVENTS.iter().any(|(k, _)| *k == id) {
+            return Err(LanguageError::UnknownEvent(id.to_string()));
+        }
+        self.events.insert(id.to_string(), text.into());
+        Ok(())
+    }
+
+    /// Shorthand for labels which always exist.
+    pub(crate) fn text(&self, key: &str) -> &str {
+        self.label(key).unwrap_or_default()
+    }
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::english()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Language, LanguageError, EVENTS, LABELS};
+
+    #[test]
+    fn bundled_locales() {
+        for code in Language::builtin_codes() {
+            let language = Language::builtin(code).unwrap();
+            assert_eq!(language.missing_keys(), Vec::<String>::new(), "the {code} translation is incomplete");
+            assert_eq!(language.right_to_left, code == "ar");
+        }
+        assert!(Language::builtin("xx").is_none());
+    }
+
+    #[test]
+    fn missing_keys() {
+        let language = Language::from_toml("[labels]\nround = \"Runde\"\n[events]\n333 = \"3x3x3\"").unwrap();
+        let missing = language.missing_keys();
+        assert!(!missing.contains(&"labels.round".to_string()));
+        assert!(!missing.contains(&"events.333".to_string()));
+        assert!(missing.contains(&"labels.group".to_string()));
+        assert!(missing.contains(&"events.444".to_string()));
+        assert_eq!(missing.len(), LABELS.len() + EVENTS.len() - 2);
+        assert_eq!(language.label("group"), Some("Group"));
+        assert!(matches!(Language::from_toml("[labels]\nrounds = \"Runden\""), Err(LanguageError::UnknownLabel(_))));
+        assert!(matches!(Language::from_toml("[events]\n222bf = \"2x2 blind\""), Err(LanguageError::UnknownEvent(_))));
+    }
+
+    #[test]
+    fn cutoff_alias() {
+        let language = Language::from_toml("[labels]\ncuroff = \"Zwei Versuche\"").unwrap();
+        assert_eq!(language.label("cutoff"), Some("Zwei Versuche"));
+        assert_eq!(language.label("curoff"), Some("Zwei Versuche"));
+        let language = Language::from_json(r#"{"labels": {"cutoff": "Twee pogingen"}}"#).unwrap();
+        assert_eq!(language.label("cutoff"), Some("Twee pogingen"));
+        let mut language = Language::english();
+        language.set_label("curoff", "Below").unwrap();
+        assert_eq!(language.label("cutoff"), Some("Below"));
     }
 }
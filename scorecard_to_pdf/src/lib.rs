@@ -3,5 +3,28 @@ mod scorecard;
 mod language;
 mod draw_scorecards;
 mod scorecard_generator;
-pub use scorecard::{scorecards_to_pdf, Scorecard, TimeLimit, Return, blank_scorecard_page};
-pub use language::Language;
+mod page_layout;
+mod template;
+mod bidi;
+mod badge;
+mod report;
+mod assignment_sheet;
+mod personal_schedule;
+mod schedule_poster;
+mod backend;
+mod bundle;
+mod image;
+pub use scorecard::{scorecards_to_pdf, write_scorecards, scorecards_to_svg, OutputKind, TruncatedText, Scorecard, Stage, RoundFormat, TimeLimit, blank_scorecard_page, ScorecardOptions};
+pub use bundle::{ScorecardBundle, ScorecardDocument, DocumentSplit};
+pub use font::{Fonts, FontError};
+pub use image::{Image, ImageError};
+pub use language::{Language, LanguageError};
+pub use page_layout::{PageLayout, PaperSize, Margins, CutMarks};
+pub use template::{ScorecardTemplate, Element, TextElement, TextPart, BoxElement, ImageElement, GridElement, QrCodeElement, DEFAULT_QR_PAYLOAD, AttemptRows, AttemptColumn, ExtraRows, TemplateError};
+pub use scorecard_generator::{Alignment, Weight, Overflow};
+pub use badge::{badges_to_pdf, Badge, BadgeRole, BadgeAssignment, StaffTask, BadgeOptions};
+pub use assignment_sheet::{assignment_sheets_to_pdf, RoundAssignments, GroupAssignments, ReportOptions};
+pub use personal_schedule::{personal_schedules_to_pdf, PersonalSchedule, ScheduleEntry};
+pub use schedule_poster::{schedule_poster_to_pdf, PosterRoom, PosterActivity, PosterOptions};
+#[cfg(feature = "png")]
+pub use scorecard::scorecards_to_png;
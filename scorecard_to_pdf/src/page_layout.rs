@@ -0,0 +1,182 @@
+/// Width and height of a scorecard in mm at scale 1. All drawing coordinates are relative to this.
+pub(crate) const CARD_WIDTH: f64 = 105.0;
+pub(crate) const CARD_HEIGHT: f64 = 99.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaperSize {
+    A3,
+    A4,
+    A5,
+    Letter,
+    /// Width and height in mm.
+    Custom(f64, f64),
+}
+
+impl PaperSize {
+    /// Width and height in mm.
+    pub fn dimensions(&self) -> (f64, f64) {
+        match self {
+            PaperSize::A3 => (297.0, 420.0),
+            PaperSize::A4 => (210.0, 297.0),
+            PaperSize::A5 => (148.0, 210.0),
+            PaperSize::Letter => (215.9, 279.4),
+            PaperSize::Custom(width, height) => (*width, *height),
+        }
+    }
+
+    /// The same paper turned sideways.
+    pub fn landscape(&self) -> PaperSize {
+        let (width, height) = self.dimensions();
+        PaperSize::Custom(width.max(height), width.min(height))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CutMarks {
+    None,
+    /// Dashed lines across the whole page between the rows and columns.
+    Dashed,
+    /// Short marks at the corners of every scorecard, for use with a guillotine.
+    Corners,
+}
+
+/// Margins in mm.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Margins {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Margins {
+    pub fn uniform(margin: f64) -> Margins {
+        Margins { top: margin, right: margin, bottom: margin, left: margin }
+    }
+}
+
+/// Describes how scorecards are placed on a page. Scorecards are scaled down uniformly if the cells
+/// of the grid are smaller than a scorecard, so nothing gets clipped on smaller paper.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageLayout {
+    pub paper: PaperSize,
+    pub rows: usize,
+    pub columns: usize,
+    pub margins: Margins,
+    pub cut_marks: CutMarks,
+}
+
+impl Default for PageLayout {
+    fn default() -> Self {
+        PageLayout::a4()
+    }
+}
+
+impl PageLayout {
+    pub fn new(paper: PaperSize, rows: usize, columns: usize, margins: Margins, cut_marks: CutMarks) -> PageLayout {
+        assert!(rows > 0 && columns > 0, "A page layout needs at least one row and one column");
+        PageLayout { paper, rows, columns, margins, cut_marks }
+    }
+
+    /// Six scorecards per A4 page.
+    pub fn a4() -> PageLayout {
+        PageLayout::new(PaperSize::A4, 3, 2, Margins::default(), CutMarks::Dashed)
+    }
+
+    /// Two scorecards per A5 page.
+    pub fn a5() -> PageLayout {
+        PageLayout::new(PaperSize::A5, 2, 1, Margins::default(), CutMarks::Dashed)
+    }
+
+    /// Six scorecards per Letter page. A small margin is kept, as most printers can not print to the edge.
+    pub fn letter() -> PageLayout {
+        PageLayout::new(PaperSize::Letter, 3, 2, Margins::uniform(4.0), CutMarks::Dashed)
+    }
+
+    pub fn per_page(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    /// Width and height of a single cell of the grid in mm.
+    pub fn cell_size(&self) -> (f64, f64) {
+        let (width, height) = self.paper.dimensions();
+        let width = width - self.margins.left - self.margins.right;
+        let height = height - self.margins.top - self.margins.bottom;
+        (width / self.columns as f64, height / self.rows as f64)
+    }
+
+    /// Factor which scorecards are scaled by to fit into a cell. Scorecards are never scaled up.
+    pub fn scale(&self) -> f64 {
+        self.scale_for((CARD_WIDTH, CARD_HEIGHT))
+    }
+
+    /// Like `scale`, for something else than a scorecard of the given width and height.
+    pub(crate) fn scale_for(&self, (card_width, card_height): (f64, f64)) -> f64 {
+        let (width, height) = self.cell_size();
+        (width / card_width).min(height / card_height).min(1.0)
+    }
+
+    /// Top left corner of the cell at the given position in pdf coordinates, i.e. from the bottom
+    /// left of the page. Positions go left to right and then top to bottom.
+    pub(crate) fn cell_origin(&self, position: usize) -> (f64, f64) {
+        let (_, height) = self.paper.dimensions();
+        let (cell_width, cell_height) = self.cell_size();
+        let row = position / self.columns;
+        let column = position % self.columns;
+        (self.margins.left + column as f64 * cell_width, height - self.margins.top - row as f64 * cell_height)
+    }
+
+    /// Top left corner of a card of the given width and height at the given position, centred in
+    /// its cell.
+    pub(crate) fn card_origin(&self, position: usize, (card_width, card_height): (f64, f64)) -> (f64, f64) {
+        let (x, y) = self.cell_origin(position);
+        let (cell_width, cell_height) = self.cell_size();
+        let scale = self.scale_for((card_width, card_height));
+        (x + (cell_width - card_width * scale) / 2.0, y - (cell_height - card_height * scale) / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CutMarks, Margins, PageLayout, PaperSize, CARD_HEIGHT, CARD_WIDTH};
+
+    fn assert_close((x, y): (f64, f64), (expected_x, expected_y): (f64, f64)) {
+        assert!((x - expected_x).abs() < 1e-9 && (y - expected_y).abs() < 1e-9, "({x}, {y}) is not ({expected_x}, {expected_y})");
+    }
+
+    #[test]
+    fn cell_origin() {
+        let a4 = PageLayout::a4();
+        assert_close(a4.cell_origin(0), (0.0, 297.0));
+        assert_close(a4.cell_origin(1), (105.0, 297.0));
+        assert_close(a4.cell_origin(2), (0.0, 198.0));
+        assert_close(a4.cell_origin(5), (105.0, 99.0));
+        let letter = PageLayout::letter();
+        assert_close(letter.cell_origin(0), (4.0, 275.4));
+        assert_close(letter.cell_origin(3), (4.0 + 207.9 / 2.0, 275.4 - 271.4 / 3.0));
+    }
+
+    #[test]
+    fn scale_for() {
+        let a4 = PageLayout::a4();
+        assert_eq!(a4.scale(), 1.0);
+        assert_eq!(a4.scale_for((210.0, 50.0)), 0.5);
+        assert_eq!(a4.scale_for((10.0, 10.0)), 1.0);
+        assert_eq!(PageLayout::a5().scale(), 1.0);
+        assert!((PageLayout::letter().scale() - 271.4 / 3.0 / 99.0).abs() < 1e-9);
+        let crowded = PageLayout::new(PaperSize::A4, 4, 4, Margins::uniform(10.0), CutMarks::Corners);
+        assert!((crowded.scale() - (190.0 / 4.0) / 105.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn card_origin() {
+        //Scorecards fill the cells of A4 exactly, and other cards are centred in them.
+        let a4 = PageLayout::a4();
+        assert_close(a4.card_origin(3, (CARD_WIDTH, CARD_HEIGHT)), (105.0, 198.0));
+        assert_close(a4.card_origin(0, (50.0, 50.0)), (27.5, 272.5));
+        //Larger cards are scaled down, and centred at their scaled size.
+        assert_close(a4.card_origin(3, (210.0, 99.0)), (105.0, 198.0 - 24.75));
+        let single = PageLayout::new(PaperSize::A4, 1, 1, Margins::default(), CutMarks::None);
+        assert_close(single.card_origin(0, (CARD_WIDTH, CARD_HEIGHT)), (52.5, 198.0));
+    }
+}
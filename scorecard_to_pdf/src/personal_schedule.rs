@@ -0,0 +1,75 @@
+use crate::assignment_sheet::ReportOptions;
+use crate::badge::StaffTask;
+use crate::language::Language;
+use crate::report::{Report, Column};
+use crate::scorecard::hex_colour;
+
+/// Everything a single person is assigned to during the competition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PersonalSchedule {
+    pub name: String,
+    pub wca_id: Option<String>,
+    /// Assignments in the order they are printed. A new table is started every time the day changes.
+    pub entries: Vec<ScheduleEntry>,
+}
+
+/// A single assignment, with its times already converted to the local time of the venue.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduleEntry {
+    /// The day of the assignment, e.g. `2024-05-04`.
+    pub day: String,
+    /// Start and end, e.g. `09:30`.
+    pub start: String,
+    pub end: String,
+    pub event: String,
+    pub round: usize,
+    pub group: usize,
+    /// None when competing.
+    pub task: Option<StaffTask>,
+    pub room: String,
+    /// The colour of the room as given in the wcif, e.g. `#304a96`.
+    pub room_colour: String,
+}
+
+/// A page per person with all their assignments, sorted by name.
+pub fn personal_schedules_to_pdf(schedules: &[PersonalSchedule], competition: &str, language: &Language, options: &ReportOptions) -> Vec<u8> {
+    let mut report = Report::new(competition, options.paper, &options.fonts, language.right_to_left);
+    let mut schedules: Vec<_> = schedules.iter().collect();
+    schedules.sort_by(|a, b| a.name.cmp(&b.name));
+    let columns = [
+        Column { header: language.text("schedule_time"), width: 0.16 },
+        Column { header: language.text("event"), width: 0.36 },
+        Column { header: language.text("group"), width: 0.1 },
+        Column { header: language.text("task"), width: 0.16 },
+        Column { header: language.text("room"), width: 0.22 },
+    ];
+
+    for schedule in schedules {
+        report.title = match &schedule.wca_id {
+            Some(id) => format!("{competition} | {} ({id})", schedule.name),
+            None => format!("{competition} | {}", schedule.name),
+        };
+        report.new_page();
+        for day in schedule.entries.chunk_by(|a, b| a.day == b.day) {
+            let rows: Vec<_> = day.iter()
+                .map(|entry| {
+                    let event = language.event(&entry.event).unwrap_or(&entry.event);
+                    let task = match &entry.task {
+                        Some(task) => task.name(language),
+                        None => language.text("competitor"),
+                    };
+                    vec![
+                        format!("{}-{}", entry.start, entry.end),
+                        format!("{event} ({} {})", language.text("round"), entry.round),
+                        entry.group.to_string(),
+                        task.to_string(),
+                        entry.room.clone(),
+                    ]
+                })
+                .collect();
+            let colours: Vec<_> = day.iter().map(|entry| hex_colour(&entry.room_colour)).collect();
+            report.coloured_table(&day[0].day, &columns, &rows, &colours);
+        }
+    }
+    report.pdf()
+}
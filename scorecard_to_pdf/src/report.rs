@@ -0,0 +1,99 @@
+use crate::font::Fonts;
+use crate::page_layout::{PageLayout, PaperSize, Margins, CutMarks};
+use crate::scorecard_generator::{ScorecardGenerator, TextFit, Overflow, Alignment::*, Weight::*};
+
+const MARGIN: f64 = 10.0;
+const TITLE_HEIGHT: f64 = 12.0;
+const SUBTITLE_HEIGHT: f64 = 8.0;
+const ROW_HEIGHT: f64 = 6.0;
+const TABLE_GAP: f64 = 5.0;
+const FONT_SIZE: f64 = 9.0;
+const MARKER_WIDTH: f64 = 2.5;
+
+/// Writes full pages of tables. Tables which do not fit on a page are continued on the next, where
+/// the title of the page, the name of the table and the column headers are repeated.
+pub(crate) struct Report<'a> {
+    generator: ScorecardGenerator<'a>,
+    width: f64,
+    height: f64,
+    page: Option<usize>,
+    y: f64,
+    pub title: String,
+}
+
+/// A column of a table, with its width as a fraction of the page width.
+pub(crate) struct Column<'a> {
+    pub header: &'a str,
+    pub width: f64,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(competition: &'a str, paper: PaperSize, fonts: &Fonts, right_to_left: bool) -> Report<'a> {
+        let layout = PageLayout::new(paper, 1, 1, Margins::uniform(MARGIN), CutMarks::None);
+        let (width, height) = layout.cell_size();
+        let generator = ScorecardGenerator::with_card_size(competition, &layout, fonts, right_to_left, (width, height));
+        Report { generator, width, height, page: None, y: 0.0, title: String::new() }
+    }
+
+    /// Starts a new page with the current title.
+    pub fn new_page(&mut self) {
+        let page = self.page.map_or(0, |page| page + 1);
+        self.generator.set_page(page);
+        self.generator.set_position(0);
+        self.page = Some(page);
+        let title = self.title.clone();
+        self.generator.write(&title, 0.0, 6.0, 14.0, Left, Bold);
+        self.y = TITLE_HEIGHT;
+    }
+
+    /// Writes a table below whatever is already on the page. The name of the table, its headers and
+    /// the first row are always kept together.
+    pub fn table(&mut self, name: &str, columns: &[Column], rows: &[Vec<String>]) {
+        self.coloured_table(name, columns, rows, &[]);
+    }
+
+    /// Like `table`, with a coloured marker at the start of every row which has a colour.
+    pub fn coloured_table(&mut self, name: &str, columns: &[Column], rows: &[Vec<String>], colours: &[Option<(f64, f64, f64)>]) {
+        if self.page.is_none() || self.y + SUBTITLE_HEIGHT + 2.0 * ROW_HEIGHT > self.height {
+            self.new_page();
+        }
+        self.table_header(name, columns);
+        for (idx, row) in rows.iter().enumerate() {
+            if self.y + ROW_HEIGHT > self.height {
+                self.new_page();
+                self.table_header(name, columns);
+            }
+            self.row(columns, row, false, colours.get(idx).copied().flatten());
+        }
+        self.y += TABLE_GAP;
+    }
+
+    fn table_header(&mut self, name: &str, columns: &[Column]) {
+        self.generator.write(name, 0.0, self.y + SUBTITLE_HEIGHT - 2.5, 11.0, Left, Bold);
+        self.y += SUBTITLE_HEIGHT;
+        let headers: Vec<String> = columns.iter().map(|column| column.header.to_string()).collect();
+        self.row(columns, &headers, true, None);
+    }
+
+    fn row(&mut self, columns: &[Column], cells: &[String], header: bool, colour: Option<(f64, f64, f64)>) {
+        let weight = if header { Bold } else { Normal };
+        let mut x = 0.0;
+        for (idx, (column, cell)) in columns.iter().zip(cells).enumerate() {
+            let width = column.width * self.width;
+            let mut indent = 1.5;
+            if let (0, Some(colour)) = (idx, colour) {
+                self.generator.fill_square(x, self.y, MARKER_WIDTH, ROW_HEIGHT, colour);
+                indent += MARKER_WIDTH;
+            }
+            self.generator.draw_square(x, self.y, width, ROW_HEIGHT);
+            let fit = TextFit { max_width: width - indent - 1.0, min_size: FONT_SIZE * 2.0 / 3.0, overflow: Overflow::Ellipsis };
+            self.generator.write_fitted(x + indent, self.y + ROW_HEIGHT - 1.8, FONT_SIZE, Left, &[(cell, weight)], fit);
+            x += width;
+        }
+        self.y += ROW_HEIGHT;
+    }
+
+    pub fn pdf(self) -> Vec<u8> {
+        self.generator.into_backend().into_bytes().unwrap()
+    }
+}
@@ -0,0 +1,135 @@
+use crate::font::Fonts;
+use crate::language::Language;
+use crate::page_layout::{PageLayout, PaperSize, Margins, CutMarks};
+use crate::scorecard::hex_colour;
+use crate::backend::PT;
+use crate::scorecard_generator::{ScorecardGenerator, TextFit, Overflow, Weight, Alignment::*, Weight::*};
+
+const MARGIN: f64 = 10.0;
+const TITLE_HEIGHT: f64 = 14.0;
+const HEADER_HEIGHT: f64 = 12.0;
+const AXIS_WIDTH: f64 = 14.0;
+/// Used for rooms without a valid colour.
+const GREY: (f64, f64, f64) = (0.6, 0.6, 0.6);
+
+/// A room with its activities, which make up a column of the poster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PosterRoom {
+    pub name: String,
+    /// The colour of the room as given in the wcif, e.g. `#304a96`.
+    pub colour: String,
+    pub activities: Vec<PosterActivity>,
+}
+
+/// An activity with its times in the local time of the venue.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PosterActivity {
+    pub name: String,
+    pub activity_code: String,
+    /// The day of the activity, e.g. `2024-05-04`. Every day gets its own page.
+    pub day: String,
+    /// Start and end in minutes after midnight.
+    pub start: u32,
+    pub end: u32,
+    /// Groups of the activity, drawn as smaller blocks inside it.
+    pub children: Vec<PosterActivity>,
+}
+
+#[derive(Clone)]
+pub struct PosterOptions {
+    pub paper: PaperSize,
+    pub fonts: Fonts,
+}
+
+impl Default for PosterOptions {
+    /// Landscape A3, to hang on the wall of the venue.
+    fn default() -> Self {
+        PosterOptions { paper: PaperSize::A3.landscape(), fonts: Fonts::default() }
+    }
+}
+
+/// A timetable with a column per room and a page per day.
+pub fn schedule_poster_to_pdf(rooms: &[PosterRoom], competition: &str, language: &Language, options: &PosterOptions) -> Vec<u8> {
+    let layout = PageLayout::new(options.paper, 1, 1, Margins::uniform(MARGIN), CutMarks::None);
+    let size = layout.cell_size();
+    let mut generator = ScorecardGenerator::with_card_size(competition, &layout, &options.fonts, language.right_to_left, size);
+
+    let mut days: Vec<&str> = rooms.iter()
+        .flat_map(|room| room.activities.iter().map(|activity| activity.day.as_str()))
+        .collect();
+    days.sort();
+    days.dedup();
+    for (page, day) in days.into_iter().enumerate() {
+        generator.set_page(page);
+        generator.set_position(0);
+        draw_day(&mut generator, rooms, day, language, size);
+    }
+    generator.into_backend().into_bytes().unwrap()
+}
+
+fn draw_day(generator: &mut ScorecardGenerator, rooms: &[PosterRoom], day: &str, language: &Language, (width, height): (f64, f64)) {
+    let title = format!("{} | {day}", generator.get_competition_name());
+    generator.write(&title, 0.0, 8.0, 20.0, Left, Bold);
+
+    //The time axis is given by the first and last activity of the day, rounded to whole hours.
+    let activities = || rooms.iter().flat_map(|room| room.activities.iter().filter(|activity| activity.day == day));
+    let first = activities().map(|activity| activity.start).min().unwrap_or(0) / 60 * 60;
+    let last = activities().map(|activity| activity.end.max(activity.start + 1)).max().unwrap_or(0).div_ceil(60) * 60;
+    let top = TITLE_HEIGHT + HEADER_HEIGHT;
+    let per_minute = (height - top) / (last - first) as f64;
+    let y_of = |minute: u32| top + (minute.clamp(first, last) - first) as f64 * per_minute;
+
+    for hour in (first..=last).step_by(60) {
+        let y = y_of(hour);
+        generator.draw_line(AXIS_WIDTH, y, width, y, 0.2);
+        generator.write(&format!("{:02}:00", hour / 60), AXIS_WIDTH - 1.5, y + 1.2, 8.0, Right, Normal);
+    }
+
+    let column_width = (width - AXIS_WIDTH) / rooms.len() as f64;
+    for (idx, room) in rooms.iter().enumerate() {
+        let x = AXIS_WIDTH + idx as f64 * column_width;
+        let colour = hex_colour(&room.colour).unwrap_or(GREY);
+        let fit = TextFit { max_width: column_width - 2.0, min_size: 4.0, overflow: Overflow::Ellipsis };
+        generator.write_fitted(x + column_width / 2.0, TITLE_HEIGHT + 6.0, 12.0, Center, &[(&room.name, Bold)], fit);
+        generator.fill_square(x + 0.5, TITLE_HEIGHT + 8.0, column_width - 1.0, 3.0, colour);
+
+        for activity in room.activities.iter().filter(|activity| activity.day == day) {
+            let (y1, y2) = (y_of(activity.start), y_of(activity.end));
+            block(generator, &activity.name, (x + 0.5, y1, column_width - 1.0, y2 - y1), tint(colour, 0.7), Bold);
+
+            //Groups are drawn on the right, so the name of the round stays readable.
+            let child_x = x + column_width * 0.4;
+            for child in &activity.children {
+                let name = match group_number(&child.activity_code) {
+                    Some(group) => format!("{} {group}", language.text("group")),
+                    None => child.name.clone(),
+                };
+                let (y1, y2) = (y_of(child.start), y_of(child.end));
+                block(generator, &name, (child_x, y1, x + column_width - 1.5 - child_x, y2 - y1), tint(colour, 0.9), Normal);
+            }
+        }
+    }
+}
+
+/// A filled and outlined block with its name written at the top, as large as it fits.
+fn block(generator: &mut ScorecardGenerator, name: &str, (x, y, width, height): (f64, f64, f64, f64), colour: (f64, f64, f64), weight: Weight) {
+    generator.fill_square(x, y, width, height, colour);
+    generator.draw_square(x, y, width, height);
+    //Text is kept inside short blocks as well, and left out where it would be too small to read.
+    let max_size = (height * 0.8 / PT).min(10.0);
+    if max_size >= 3.0 {
+        let fit = TextFit { max_width: width - 2.0, min_size: max_size.min(4.0), overflow: Overflow::Ellipsis };
+        let size = generator.fitted_size(&[(name, weight)], max_size, fit);
+        generator.write_fitted(x + 1.0, y + size * PT + 0.8, size, Left, &[(name, weight)], fit);
+    }
+}
+
+/// Mixes the colour with white, so black text stays readable on dark rooms.
+fn tint((r, g, b): (f64, f64, f64), amount: f64) -> (f64, f64, f64) {
+    (r + (1.0 - r) * amount, g + (1.0 - g) * amount, b + (1.0 - b) * amount)
+}
+
+/// The group number of an activity code, e.g. 2 for 333-r1-g2.
+fn group_number(activity_code: &str) -> Option<usize> {
+    activity_code.rsplit('-').next()?.strip_prefix('g')?.parse().ok()
+}
@@ -1,9 +1,15 @@
-use printpdf::PdfDocumentReference;
-use std::collections::HashMap;
-use std::io::Write;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufWriter, Write};
+use zip::ZipWriter;
 use crate::language::Language;
-use crate::draw_scorecards::draw_scorecard;
+use crate::draw_scorecards::{draw_scorecard, fill_placeholders};
+use crate::bundle::{zip_options, DocumentSplit, ScorecardBundle, ScorecardDocument};
 use crate::scorecard_generator::ScorecardGenerator;
+use crate::page_layout::{PageLayout, CARD_WIDTH, CARD_HEIGHT};
+use crate::template::ScorecardTemplate;
+use crate::font::{Fonts, LoadedFonts};
+use crate::image::Image;
+use crate::backend::{Backend, PdfBackend, SvgBackend};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Scorecard<'a> {
@@ -12,7 +18,87 @@ pub struct Scorecard<'a> {
     pub group: usize,
     pub station: Option<usize>,
     pub id: usize,
-    pub stage: Option<u32>
+    pub stage: Option<Stage<'a>>,
+    /// Format of the round. Without it the number of attempts is guessed from the event.
+    pub format: Option<RoundFormat>,
+}
+
+/// The stage a scorecard is competed on. Scorecards are sorted into a stack per stage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Stage<'a> {
+    /// Stages are numbered from 0, but shown from 1 when they have no name.
+    pub number: u32,
+    pub name: Option<&'a str>,
+    /// Colour of the stripe on the scorecards, e.g. `#c0392b`.
+    pub colour: Option<&'a str>,
+}
+
+impl<'a> Stage<'a> {
+    pub fn new(number: u32) -> Stage<'a> {
+        Stage { number, name: None, colour: None }
+    }
+
+    /// The name of the stage, or its number when it has none.
+    pub fn label(&self) -> String {
+        match self.name {
+            Some(name) => name.to_string(),
+            None => (self.number + 1).to_string()
+        }
+    }
+}
+
+/// Parses colours written as `#rrggbb`, with or without the `#`.
+pub(crate) fn hex_colour(hex: &str) -> Option<(f64, f64, f64)> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok().map(|v| v as f64 / 255.0);
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Text which did not fit on a scorecard, even at the smallest font size, and was cut off.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TruncatedText {
+    pub event: String,
+    pub round: usize,
+    pub group: usize,
+    /// Registrant id of the competitor.
+    pub id: usize,
+    /// The whole text, before it was cut off.
+    pub text: String,
+}
+
+/// How many attempts a round has and after which attempt the cutoff is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RoundFormat {
+    pub attempts: usize,
+    /// Number of attempts to make the cutoff in. Only the attempts after these are behind the cutoff.
+    pub cutoff: Option<usize>,
+}
+
+impl RoundFormat {
+    /// From the format of a round in the WCIF, i.e. `'1'`, `'2'`, `'3'`, `'m'` or `'a'`, and the
+    /// number of attempts of its cutoff.
+    pub fn from_wcif(format: char, cutoff: Option<usize>) -> Option<RoundFormat> {
+        let attempts = match format {
+            '1' => 1,
+            '2' => 2,
+            '3' | 'm' => 3,
+            'a' => 5,
+            _ => return None
+        };
+        Some(RoundFormat { attempts, cutoff: cutoff.filter(|cutoff| *cutoff < attempts) })
+    }
+
+    /// The usual format of the event, used when the round is not known.
+    pub fn guess(event: &str) -> RoundFormat {
+        let attempts = match event {
+            "666" | "777" | "333fm" | "333mbf" | "333bf" | "444bf" | "555bf" => 3,
+            _ => 5
+        };
+        RoundFormat { attempts, cutoff: None }
+    }
 }
 
 pub enum TimeLimit {
@@ -24,9 +110,55 @@ pub enum TimeLimit {
     None
 }
 
-pub enum Return {
-    Zip(Vec<u8>),
-    Pdf(Vec<u8>)
+/// Everything about how scorecards look apart from the language.
+#[derive(Clone)]
+pub struct ScorecardOptions {
+    pub layout: PageLayout,
+    pub template: ScorecardTemplate,
+    /// Templates used instead of `template` for some events, keyed by event id. By default fewest
+    /// moves and multi-blind have their own.
+    pub event_templates: HashMap<String, ScorecardTemplate>,
+    pub fonts: Fonts,
+    /// Drawn in the logo area of the templates, next to the name of the competition.
+    pub logo: Option<Image>,
+    /// Images for the image elements of the templates, keyed by their names.
+    pub images: HashMap<String, Image>,
+    /// WCA id of the competition, written in the QR codes so scans can be matched to it. Scorecards
+    /// made from a wcif fill it in themselves, scorecards made from csv files leave it empty unless
+    /// it is set here.
+    pub competition_id: String,
+    pub split: DocumentSplit,
+    /// File names of the documents. `{stage}` is the name or number of the stage, `Missing_stage` for
+    /// scorecards without one and `All` for a single document. `{competition}` and `{events}` are
+    /// the name of the competition without spaces and the event ids in the document. Characters
+    /// which are not allowed in file names are replaced by `_`, and names used twice get a number.
+    pub document_name: String,
+}
+
+impl Default for ScorecardOptions {
+    fn default() -> Self {
+        let event_templates = [
+            ("333fm".to_string(), ScorecardTemplate::fewest_moves()),
+            ("333mbf".to_string(), ScorecardTemplate::multi_blind()),
+        ].into_iter().collect();
+        ScorecardOptions {
+            layout: PageLayout::default(),
+            template: ScorecardTemplate::default(),
+            event_templates,
+            fonts: Fonts::default(),
+            logo: None,
+            images: HashMap::new(),
+            competition_id: String::new(),
+            split: DocumentSplit::ByStage,
+            document_name: "{stage}_scorecards.pdf".to_string(),
+        }
+    }
+}
+
+impl ScorecardOptions {
+    pub fn template_for(&self, event: &str) -> &ScorecardTemplate {
+        self.event_templates.get(event).unwrap_or(&self.template)
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -63,85 +195,357 @@ impl<'a> MaybeScorecard<'a> {
         self.internal_or_default(|s| s.id.to_string(), "".to_string())
     }
 
+    pub fn stage(&self) -> Option<Stage<'a>> {
+        match self {
+            MaybeScorecard::Blank => None,
+            MaybeScorecard::Normal(s) => s.stage,
+        }
+    }
+
     pub fn name(&'a self, map: &'a HashMap<usize, String>) -> &'a str {
         self.internal_or_default(|s| &map[&s.id], "")
     }
 
-    pub fn limit(&'a self, limit: &'a HashMap<&str, TimeLimit>) -> &'a TimeLimit {
-        self.internal_or_default(|s| &limit.get(s.event).unwrap_or(&TimeLimit::None), &TimeLimit::None)
+    pub fn format(&self) -> RoundFormat {
+        self.internal_or_default(|s| s.format.unwrap_or_else(|| RoundFormat::guess(s.event)), RoundFormat::guess(""))
+    }
+
+    pub fn limit(&'a self, limit: &'a HashMap<(&str, usize), TimeLimit>) -> &'a TimeLimit {
+        self.internal_or_default(|s| limit.get(&(s.event, s.round)).unwrap_or(&TimeLimit::None), &TimeLimit::None)
     }
 }
 
-pub fn scorecards_to_pdf(scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<&str, TimeLimit>, language: Language) -> Return {
-    let mut buckets = HashMap::new();
-    for scorecard in scorecards {
-        let key = scorecard.stage;
-        match buckets.get_mut(&key) {
-            None => {
-                buckets.insert(key, vec![scorecard]);
-            }
-            Some(v) => {
-                v.push(scorecard);
-            }
+/// The scorecards as pdfs, split into documents as given by the options. Time limits are given by
+/// event id and round.
+pub fn scorecards_to_pdf(scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: Language, options: &ScorecardOptions) -> ScorecardBundle {
+    let fonts = options.fonts.load();
+    let mut warnings = vec![];
+    let documents = plan_documents(scorecards, competition, options).into_iter()
+        .map(|document| {
+            let mut pdf = vec![];
+            let (doc, cut) = scorecards_to_pdf_internal(&document.stacks, competition, map, limits, &language, options, &fonts);
+            save(doc, &mut pdf).unwrap();
+            warnings.extend(cut);
+            ScorecardDocument { name: document.name, stage: document.stage, events: document.events, pages: document.pages, pdf }
+        })
+        .collect();
+    ScorecardBundle { documents, warnings }
+}
+
+/// What `write_scorecards` wrote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputKind {
+    Pdf,
+    Zip,
+}
+
+/// Writes the scorecards to `writer` as it goes, e.g. to a file or a http response. A single
+/// document is written as a pdf, otherwise all documents are written into a zip. Only one document
+/// is kept in memory at a time, and the fonts are loaded once for all of them. Returns what was
+/// written and the texts which were cut off.
+pub fn write_scorecards<W: Write>(scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: &Language, options: &ScorecardOptions, mut writer: W) -> std::io::Result<(OutputKind, Vec<TruncatedText>)> {
+    let documents = plan_documents(scorecards, competition, options);
+    let fonts = options.fonts.load();
+    if let [document] = documents.as_slice() {
+        let (pdf, warnings) = scorecards_to_pdf_internal(&document.stacks, competition, map, limits, language, options, &fonts);
+        save(pdf, &mut writer)?;
+        Ok((OutputKind::Pdf, warnings))
+    }
+    else {
+        let mut warnings = vec![];
+        let mut zip = ZipWriter::new_stream(writer);
+        for document in documents {
+            zip.start_file(document.name.as_str(), zip_options())?;
+            let (pdf, cut) = scorecards_to_pdf_internal(&document.stacks, competition, map, limits, language, options, &fonts);
+            save(pdf, &mut zip)?;
+            warnings.extend(cut);
         }
+        zip.finish()?;
+        Ok((OutputKind::Zip, warnings))
     }
-    if buckets.len() == 1 {
-        Return::Pdf(scorecards_to_pdf_internal(buckets.into_values().next().unwrap(), competition, map, limits, &language).save_to_bytes().unwrap())
+}
+
+/// A document before it is drawn. Every stack of scorecards starts on a new page.
+struct PlannedDocument<'s> {
+    name: String,
+    stage: Option<String>,
+    events: Vec<String>,
+    pages: usize,
+    stacks: Vec<Vec<Scorecard<'s>>>,
+}
+
+fn plan_documents<'s>(scorecards: Vec<Scorecard<'s>>, competition: &str, options: &ScorecardOptions) -> Vec<PlannedDocument<'s>> {
+    let mut stages: BTreeMap<Option<Stage>, Vec<Scorecard>> = BTreeMap::new();
+    for scorecard in scorecards {
+        stages.entry(scorecard.stage).or_default().push(scorecard);
     }
-    else {
-        let mut buf = vec![];
-        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(&mut buf));
-        for (key, bucket) in buckets {
-            let pdf = scorecards_to_pdf_internal(bucket, competition, map, limits, &language);
-            zip.start_file(match key {
-                None => "Missing_stage_scorecards.pdf".to_string(),
-                Some(v) => format!("{v}_scorecards.pdf")
-            }, zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored)).unwrap();
-            zip.write(&pdf.save_to_bytes().unwrap()).unwrap();
+    let per_page = options.layout.per_page();
+    let document = |stage_name: &str, stage: Option<String>, stacks: Vec<Vec<Scorecard<'s>>>| {
+        let mut events: Vec<String> = vec![];
+        for scorecard in stacks.iter().flatten() {
+            if !events.iter().any(|event| event == scorecard.event) {
+                events.push(scorecard.event.to_string());
+            }
+        }
+        let name = fill_placeholders(&options.document_name, |key| match key {
+            "competition" => Some(competition.split_ascii_whitespace().collect()),
+            "stage" => Some(stage_name.to_string()),
+            "events" => Some(events.join("-")),
+            _ => None
+        });
+        let pages = stacks.iter().map(|stack| sheets(stack, options).len().div_ceil(per_page)).sum();
+        PlannedDocument { name, stage, events, pages, stacks }
+    };
+    let mut documents: Vec<_> = match options.split {
+        DocumentSplit::ByStage => stages.into_iter()
+            .map(|(stage, scorecards)| match stage {
+                None => document("Missing_stage", None, vec![scorecards]),
+                Some(v) => document(&v.label(), Some(v.label()), vec![scorecards]),
+            })
+            .collect(),
+        DocumentSplit::Single => {
+            let stage = match stages.keys().collect::<Vec<_>>().as_slice() {
+                [stage] => stage.map(|stage| stage.label()),
+                _ => None
+            };
+            vec![document("All", stage, stages.into_values().collect())]
         }
-        zip.finish().unwrap();
-        drop(zip);
-        Return::Zip(buf)
+    };
+    let mut names = vec![];
+    for document in documents.iter_mut() {
+        document.name = unique_file_name(&document.name, &names);
+        names.push(document.name.clone());
     }
+    documents
+}
+
+/// Makes a name safe to use as a file name, in a zip or a directory, and different from the names
+/// already taken by adding a number before the extension. Names differing only in case are taken as
+/// the same, as they are on some file systems.
+fn unique_file_name(name: &str, taken: &[String]) -> String {
+    let name: String = name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c
+        })
+        .collect();
+    //Names starting with a dot are hidden, and `..` would point outside the directory.
+    let name = match name.trim_start_matches('.').trim() {
+        "" => "scorecards.pdf".to_string(),
+        name => name.to_string()
+    };
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+        _ => (name.as_str(), String::new())
+    };
+    (1..)
+        .map(|n| match n {
+            1 => name.clone(),
+            n => format!("{stem}_{n}{extension}")
+        })
+        .find(|name| !taken.iter().any(|taken| taken.to_lowercase() == name.to_lowercase()))
+        .unwrap()
+}
+
+fn save<W: Write>(pdf: PdfBackend, writer: W) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    pdf.save(&mut writer)?;
+    writer.flush()
+}
+
+fn scorecards_to_pdf_internal(stacks: &[Vec<Scorecard>], competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: &Language, options: &ScorecardOptions, fonts: &LoadedFonts) -> (PdfBackend, Vec<TruncatedText>) {
+    let mut scorecard_generator = ScorecardGenerator::with_loaded_fonts(competition, &options.layout, fonts, language.right_to_left, (CARD_WIDTH, CARD_HEIGHT));
+    let mut page = 0;
+    let mut warnings = vec![];
+    for stack in stacks {
+        let (pages, cut) = draw_scorecards(&mut scorecard_generator, stack, page, map, limits, language, options);
+        page += pages;
+        warnings.extend(cut);
+    }
+    (scorecard_generator.into_backend(), warnings)
+}
+
+/// Every page of the scorecards as an svg image, laid out exactly as in the pdf, e.g. to preview
+/// them in a browser before printing.
+pub fn scorecards_to_svg(scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: &Language, options: &ScorecardOptions) -> Vec<String> {
+    let fonts = options.fonts.load();
+    let mut scorecard_generator = ScorecardGenerator::with_backend(SvgBackend::new(), competition, &options.layout, &fonts, language.right_to_left, (CARD_WIDTH, CARD_HEIGHT));
+    draw_scorecards(&mut scorecard_generator, &scorecards, 0, map, limits, language, options);
+    scorecard_generator.into_backend().pages()
+}
+
+/// Like `scorecards_to_svg`, as png images with the given resolution in dots per inch.
+#[cfg(feature = "png")]
+pub fn scorecards_to_png(scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: &Language, options: &ScorecardOptions, dpi: f64) -> Result<Vec<Vec<u8>>, String> {
+    let fonts = options.fonts.load();
+    let font_files: Vec<&[u8]> = fonts.stack(crate::Weight::Normal).files().chain(fonts.stack(crate::Weight::Bold).files()).collect();
+    scorecards_to_svg(scorecards, competition, map, limits, language, options).iter()
+        .map(|svg| crate::backend::svg_to_png(svg, &font_files, dpi))
+        .collect()
 }
 
-pub fn scorecards_to_pdf_internal(scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<&str, TimeLimit>, language: &Language) -> PdfDocumentReference {
-    let mut scorecard_generator = ScorecardGenerator::new(competition);
-    let mut scorecards: Vec<MaybeScorecard> = scorecards.into_iter().map(|scorecard|MaybeScorecard::Normal(scorecard)).collect();
-    while scorecards.len() % 6 != 0 {
-        scorecards.push(MaybeScorecard::Blank);
+/// Draws the scorecards from the given page on. Returns the number of pages they take and the texts
+/// which were cut off.
+#[allow(clippy::too_many_arguments)]
+fn draw_scorecards<B: Backend>(scorecard_generator: &mut ScorecardGenerator<B>, scorecards: &[Scorecard], first_page: usize, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: &Language, options: &ScorecardOptions) -> (usize, Vec<TruncatedText>) {
+    let per_page = options.layout.per_page();
+    let mut scorecards = sheets(scorecards, options);
+    while scorecards.len() % per_page != 0 {
+        scorecards.push((MaybeScorecard::Blank, None));
     }
 
-    let n_pages = scorecards.len() / 6;
+    //Order the scorecards such that cutting the printed stack and putting the piles on top of each
+    //other gives the scorecards in the original order.
+    let n_pages = scorecards.len() / per_page;
     scorecards = (0..scorecards.len()).map(|x|{
-        let page = x / 6;
-        let pos = x % 6;
+        let page = x / per_page;
+        let pos = x % per_page;
         scorecards[pos * n_pages + page]
-    }).collect::<Vec<MaybeScorecard>>();
+    }).collect::<Vec<_>>();
 
     let mut scorecard_pages = vec![];
     for i in 0..n_pages {
-        scorecard_pages.push(&scorecards[(i * 6)..(i * 6) + 6])
+        scorecard_pages.push(&scorecards[(i * per_page)..(i * per_page) + per_page])
     }
 
+    let mut warnings = vec![];
     for (page, scorecards) in scorecard_pages.into_iter().enumerate() {
-        scorecard_generator.set_page(page);
-        for (position, scorecard) in scorecards.into_iter().enumerate() {
+        scorecard_generator.set_page(first_page + page);
+        for (position, (scorecard, attempt)) in scorecards.iter().enumerate() {
             scorecard_generator.set_position(position);
-            draw_scorecard(&mut scorecard_generator, scorecard, map, limits, &language);
+            let cut = draw_scorecard(scorecard_generator, scorecard, *attempt, map, limits, language, options);
+            if let MaybeScorecard::Normal(scorecard) = scorecard {
+                warnings.extend(cut.into_iter().map(|text| TruncatedText {
+                    event: scorecard.event.to_string(),
+                    round: scorecard.round,
+                    group: scorecard.group,
+                    id: scorecard.id,
+                    text,
+                }));
+            }
         }
     }
-    scorecard_generator.doc()
+    (n_pages, warnings)
+}
+
+/// The sheets of the scorecards in order: one per scorecard, or one per attempt for templates with
+/// a sheet per attempt.
+fn sheets<'s>(scorecards: &[Scorecard<'s>], options: &ScorecardOptions) -> Vec<(MaybeScorecard<'s>, Option<usize>)> {
+    scorecards.iter()
+        .flat_map(|scorecard| {
+            let scorecard = MaybeScorecard::Normal(*scorecard);
+            let attempts = match options.template_for(scorecard.event()).sheet_per_attempt {
+                true => (1..=scorecard.format().attempts).map(Some).collect(),
+                false => vec![None]
+            };
+            attempts.into_iter().map(move |attempt| (scorecard, attempt))
+        })
+        .collect()
 }
 
-pub fn blank_scorecard_page(competition: &str, language: &Language) -> Return {
-    let mut scorecard_generator = ScorecardGenerator::new(competition);
+pub fn blank_scorecard_page(competition: &str, language: &Language, options: &ScorecardOptions) -> ScorecardBundle {
+    let mut scorecard_generator = ScorecardGenerator::new(competition, &options.layout, &options.fonts, language.right_to_left);
     scorecard_generator.set_page(0);
     let map = HashMap::new();
     let limits = HashMap::new();
-    for i in 0..6 {
+    for i in 0..options.layout.per_page() {
         scorecard_generator.set_position(i);
-        draw_scorecard(&mut scorecard_generator, &MaybeScorecard::Blank, &map, &limits, language)
+        draw_scorecard(&mut scorecard_generator, &MaybeScorecard::Blank, None, &map, &limits, language, options);
+    }
+    let document = ScorecardDocument {
+        name: "blank_scorecards.pdf".to_string(),
+        stage: None,
+        events: vec![],
+        pages: 1,
+        pdf: scorecard_generator.into_backend().into_bytes().unwrap(),
+    };
+    ScorecardBundle { documents: vec![document], warnings: vec![] }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::io::{Cursor, Read};
+    use crate::language::Language;
+    use crate::template::{Element, ScorecardTemplate};
+    use super::{plan_documents, sheets, unique_file_name, write_scorecards, DocumentSplit, OutputKind, RoundFormat, Scorecard, ScorecardOptions, Stage};
+
+    #[test]
+    fn formats_from_wcif() {
+        assert_eq!(RoundFormat::from_wcif('1', None), Some(RoundFormat { attempts: 1, cutoff: None }));
+        assert_eq!(RoundFormat::from_wcif('2', None), Some(RoundFormat { attempts: 2, cutoff: None }));
+        assert_eq!(RoundFormat::from_wcif('3', None), Some(RoundFormat { attempts: 3, cutoff: None }));
+        assert_eq!(RoundFormat::from_wcif('m', Some(1)), Some(RoundFormat { attempts: 3, cutoff: Some(1) }));
+        assert_eq!(RoundFormat::from_wcif('a', Some(2)), Some(RoundFormat { attempts: 5, cutoff: Some(2) }));
+        //A cutoff after the last attempt does not cut anything off.
+        assert_eq!(RoundFormat::from_wcif('2', Some(2)), Some(RoundFormat { attempts: 2, cutoff: None }));
+        assert_eq!(RoundFormat::from_wcif('1', Some(1)), Some(RoundFormat { attempts: 1, cutoff: None }));
+        assert_eq!(RoundFormat::from_wcif('x', None), None);
+        assert_eq!(RoundFormat::from_wcif('5', Some(2)), None);
+    }
+
+    #[test]
+    fn guessed_formats() {
+        assert_eq!(RoundFormat::guess("333"), RoundFormat { attempts: 5, cutoff: None });
+        assert_eq!(RoundFormat::guess("555bf"), RoundFormat { attempts: 3, cutoff: None });
+        assert_eq!(RoundFormat::guess("666"), RoundFormat { attempts: 3, cutoff: None });
+    }
+
+    #[test]
+    fn fewest_moves_rows_above_grid() {
+        let format = RoundFormat::guess("333fm");
+        assert_eq!(format, RoundFormat { attempts: 3, cutoff: None });
+        let template = ScorecardTemplate::fewest_moves();
+        let rows = template.elements.iter().find_map(|e| match e { Element::AttemptRows(rows) => Some(rows), _ => None }).unwrap();
+        let grid = template.elements.iter().find_map(|e| match e { Element::Grid(grid) => Some(grid), _ => None }).unwrap();
+        let bottom = rows.y + (format.attempts - 1) as f64 * rows.distance + rows.height;
+        assert!(bottom < grid.y, "attempt rows end at {bottom} mm, below the top of the grid at {} mm", grid.y);
     }
-    Return::Pdf(scorecard_generator.doc().save_to_bytes().unwrap())
-}
\ No newline at end of file
+
+    #[test]
+    fn fewest_moves_sheet_per_attempt() {
+        let scorecard = |event, id| Scorecard { event, round: 1, group: 1, station: None, id, stage: None, format: None };
+        let options = ScorecardOptions { split: DocumentSplit::Single, ..ScorecardOptions::default() };
+        let attempts: Vec<_> = sheets(&[scorecard("333fm", 1), scorecard("333", 2)], &options).into_iter()
+            .map(|(scorecard, attempt)| (scorecard.id(), attempt))
+            .collect();
+        assert_eq!(attempts, vec![("1".to_string(), Some(1)), ("1".to_string(), Some(2)), ("1".to_string(), Some(3)), ("2".to_string(), None)]);
+
+        //Three competitors make nine sheets, which take two pages.
+        assert_eq!(options.layout.per_page(), 6);
+        let documents = plan_documents(vec![scorecard("333fm", 1), scorecard("333fm", 2), scorecard("333fm", 3)], "Test", &options);
+        assert_eq!(documents[0].pages, 2);
+    }
+
+    #[test]
+    fn file_names() {
+        let taken = vec!["Red_scorecards.pdf".to_string(), "red_scorecards_2.pdf".to_string()];
+        assert_eq!(unique_file_name("Blue_scorecards.pdf", &taken), "Blue_scorecards.pdf");
+        assert_eq!(unique_file_name("Red_scorecards.pdf", &taken), "Red_scorecards_3.pdf");
+        assert_eq!(unique_file_name("../Hall A/B_scorecards.pdf", &[]), "_Hall A_B_scorecards.pdf");
+        assert_eq!(unique_file_name("..", &[]), "scorecards.pdf");
+        assert_eq!(unique_file_name("scorecards", &["scorecards".to_string()]), "scorecards_2");
+    }
+
+    #[test]
+    fn streamed_zip() {
+        let scorecard = |id, stage| Scorecard { event: "333", round: 1, group: 1, station: Some(id), id, stage: Some(Stage::new(stage)), format: None };
+        let scorecards = vec![scorecard(1, 0), scorecard(2, 0), scorecard(3, 1)];
+        let map = (1..=3).map(|id| (id, format!("Competitor {id}"))).collect();
+        let mut bytes = vec![];
+        let (kind, warnings) = write_scorecards(scorecards, "Test Open", &map, &HashMap::new(), &Language::english(), &ScorecardOptions::default(), &mut bytes).unwrap();
+        assert_eq!(kind, OutputKind::Zip);
+        assert!(warnings.is_empty());
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 2);
+        for (idx, name) in ["1_scorecards.pdf", "2_scorecards.pdf"].iter().enumerate() {
+            let mut file = archive.by_index(idx).unwrap();
+            assert_eq!(file.name(), *name);
+            let mut pdf = vec![];
+            file.read_to_end(&mut pdf).unwrap();
+            assert!(pdf.starts_with(b"%PDF"));
+        }
+    }
+}
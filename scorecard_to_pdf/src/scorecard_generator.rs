@@ -1,183 +1,448 @@
-use font_kit::font::Font;
-use printpdf::{PdfDocumentReference, IndirectFontRef, PdfDocument, Point, Mm, PdfPageIndex, PdfLayerIndex, Line, PdfLayerReference, LineDashPattern, Color, Greyscale};
-
-pub struct ScorecardGenerator<'a> {
-    document: PdfDocumentReference,
-    normal_font: IndirectFontRef,
-    normal_font_width: Font,
-    bold_font: IndirectFontRef,
-    bold_font_width: Font,
+use serde::{Deserialize, Serialize};
+use crate::page_layout::{PageLayout, CutMarks, CARD_WIDTH, CARD_HEIGHT};
+use crate::bidi::visual_order;
+use crate::font::{Fonts, LoadedFonts};
+use crate::backend::{Backend, PdfBackend, TextRun, PT};
+use crate::image::Image;
+
+/// Thickness in pt of boxes and cut lines.
+const LINE_THICKNESS: f64 = 0.5;
+/// Distance in mm between the baselines of wrapped text, per pt of font size.
+const LINE_SPACING: f64 = 1.2 * PT;
+/// Ends text which is cut off.
+const ELLIPSIS: &str = "...";
+
+pub struct ScorecardGenerator<'a, B: Backend = PdfBackend> {
+    backend: B,
+    fonts: LoadedFonts,
+    /// Top left corner of the current card on the page, in mm from the top left of the page.
     offset_x: f64,
     offset_y: f64,
-    page: usize,
-    pages: Vec<(PdfPageIndex, PdfLayerIndex)>,
+    pages: usize,
     competition_name: &'a str,
+    layout: PageLayout,
+    scale: f64,
+    right_to_left: bool,
+    /// Width and height of what is drawn in every cell of the layout, at scale 1.
+    card_size: (f64, f64),
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
 pub enum Weight {
+    #[default]
     Normal,
     Bold,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
 pub enum Alignment {
+    #[default]
     Left,
     Center,
     Right,
 }
 
+/// What happens to text which is still too wide at the smallest font size.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    /// Cut off with an ellipsis.
+    #[default]
+    Ellipsis,
+    /// Continued on up to the given number of lines below, and cut off on the last of them.
+    Wrap(usize),
+}
+
+/// Bounds for text written with `ScorecardGenerator::write_fitted`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextFit {
+    pub max_width: f64,
+    /// Text is shrunk down to this font size before it is wrapped or cut off.
+    pub min_size: f64,
+    pub overflow: Overflow,
+}
+
 impl<'a> ScorecardGenerator<'a> {
-    pub fn new(competition_name: &'a str) -> ScorecardGenerator<'a> {
-        let doc = PdfDocument::empty(competition_name);
-        let (normal_font_width, normal_font) = crate::font::load_fonts(&doc, "normal");
-        let (bold_font_width, bold_font) = crate::font::load_fonts(&doc, "bold");
-        ScorecardGenerator { 
-            document: doc, 
-            normal_font, 
-            normal_font_width, 
-            bold_font, 
-            bold_font_width, 
-            offset_x: 0.0, 
-            offset_y: 0.0, 
-            page: 0, 
-            pages: vec![],
-            competition_name }
+    pub fn new(competition_name: &'a str, layout: &PageLayout, fonts: &Fonts, right_to_left: bool) -> ScorecardGenerator<'a> {
+        ScorecardGenerator::with_card_size(competition_name, layout, fonts, right_to_left, (CARD_WIDTH, CARD_HEIGHT))
+    }
+
+    /// For drawing something of another size than a scorecard, e.g. a full page.
+    pub fn with_card_size(competition_name: &'a str, layout: &PageLayout, fonts: &Fonts, right_to_left: bool, card_size: (f64, f64)) -> ScorecardGenerator<'a> {
+        ScorecardGenerator::with_loaded_fonts(competition_name, layout, &fonts.load(), right_to_left, card_size)
+    }
+
+    /// For writing several documents without parsing the fonts for every one of them.
+    pub(crate) fn with_loaded_fonts(competition_name: &'a str, layout: &PageLayout, fonts: &LoadedFonts, right_to_left: bool, card_size: (f64, f64)) -> ScorecardGenerator<'a> {
+        ScorecardGenerator::with_backend(PdfBackend::new(competition_name), competition_name, layout, fonts, right_to_left, card_size)
+    }
+}
+
+impl<'a, B: Backend> ScorecardGenerator<'a, B> {
+    /// Draws with something else than the pdf backend, e.g. svg for previews.
+    pub(crate) fn with_backend(backend: B, competition_name: &'a str, layout: &PageLayout, fonts: &LoadedFonts, right_to_left: bool, card_size: (f64, f64)) -> ScorecardGenerator<'a, B> {
+        ScorecardGenerator {
+            backend,
+            fonts: fonts.clone(),
+            offset_x: 0.0,
+            offset_y: 0.0,
+            pages: 0,
+            competition_name,
+            layout: *layout,
+            scale: layout.scale_for(card_size),
+            right_to_left,
+            card_size }
     }
 
     pub fn set_page(&mut self, page: usize) {
-        while self.pages.len() <= page {
-            let (page, layer) = self.document.add_page(Mm(210.0), Mm(297.0), "");
-            self.pages.push((page, layer));
-
-            let current_layer = self.document.get_page(page).get_layer(layer);
-            let points1 = vec![(Point::new(Mm(105.0), Mm(0.0)), false),
-                            (Point::new(Mm(105.0), Mm(297.0)), false)];
-            let points2 = vec![(Point::new(Mm(0.0), Mm(99.0)), false),
-                            (Point::new(Mm(210.0), Mm(99.0)), false)];
-            let points3 = vec![(Point::new(Mm(0.0), Mm(198.0)), false),
-                            (Point::new(Mm(210.0), Mm(198.0)), false)];
-            let line1 = line_from_points(points1);
-            let line2 = line_from_points(points2);
-            let line3 = line_from_points(points3);
-            let width = Some(5);
-            let gap = Some(10);
-            let dash_pattern = LineDashPattern::new(0, width, gap, width, gap, width, gap);
-            let outline_color = Color::Greyscale(Greyscale::new(0.0, None));
-            current_layer.set_overprint_stroke(true);
-            current_layer.set_line_dash_pattern(dash_pattern);
-            current_layer.set_outline_color(outline_color);
-            current_layer.set_outline_thickness(0.5);
-            current_layer.add_shape(line1);
-            current_layer.add_shape(line2);
-            current_layer.add_shape(line3);
-            
-            let dash_pattern = LineDashPattern::new(0, None, None, None, None, None, None);
-            current_layer.set_line_dash_pattern(dash_pattern);
+        while self.pages <= page {
+            let (width, height) = self.layout.paper.dimensions();
+            self.backend.add_page(width, height);
+            self.pages += 1;
+            let dash = match self.layout.cut_marks {
+                CutMarks::Dashed => Some((5.0, 10.0)),
+                _ => None
+            };
+            for line in cut_lines(&self.layout) {
+                self.backend.stroke(&line, false, LINE_THICKNESS, dash);
+            }
         }
-        self.page = page;
+        self.backend.set_page(page);
     }
 
     pub fn set_position(&mut self, position: usize) {
-        (self.offset_x, self.offset_y) = match position {
-            0 => (0.0, 297.0),
-            1 => (105.0, 297.0),
-            2 => (0.0, 198.0),
-            3 => (105.0, 198.0),
-            4 => (0.0, 99.0),
-            5 => (105.0, 99.0),
-            _ => unreachable!()
-        }
+        assert!(position < self.layout.per_page(), "Position {position} does not exist in the page layout");
+        let (x, y) = self.layout.card_origin(position, self.card_size);
+        let (_, height) = self.layout.paper.dimensions();
+        (self.offset_x, self.offset_y) = (x, height - y);
     }
 
-    pub fn get_current_layer(&self) -> PdfLayerReference {
-        let (page, layer) = self.pages[self.page];
-        self.document.get_page(page).get_layer(layer)
+    /// The corners of a rectangle on the page, mirrored on right to left scorecards.
+    fn rectangle(&self, mut x: f64, y: f64, width: f64, height: f64) -> [(f64, f64); 4] {
+        if self.right_to_left {
+            x = self.card_size.0 - x - width;
+        }
+        [self.point(x, y), self.point(x + width, y), self.point(x + width, y + height), self.point(x, y + height)]
     }
 
     pub fn draw_square(&mut self, x: f64, y: f64, width: f64, height: f64) {
-        let (x1, y1) = (self.offset_x, self.offset_y);
-        let points = vec![(Point::new(Mm(x + x1), Mm(y1 - y)), false),
-            (Point::new(Mm(x + x1 + width), Mm(y1 - y)), false),
-            (Point::new(Mm(x + x1 + width), Mm(y1 - y - height)), false),
-            (Point::new(Mm(x + x1), Mm(y1 - y - height)), false)];
-        let square = Line {
-            points,
-            is_closed: true,
-            has_fill: false,
-            has_stroke: true,
-            is_clipping_path: false,
+        let points = self.rectangle(x, y, width, height);
+        self.backend.stroke(&points, true, LINE_THICKNESS, None);
+    }
+
+    /// Rectangle filled with a colour given as red, green and blue from 0 to 1.
+    pub fn fill_square(&mut self, x: f64, y: f64, width: f64, height: f64, colour: (f64, f64, f64)) {
+        let points = self.rectangle(x, y, width, height);
+        self.backend.fill(&points, colour);
+    }
+
+    /// Draws the image as large as it fits in the box, centered and keeping its aspect ratio. Like
+    /// QR codes it is moved on mirrored scorecards, but not mirrored itself.
+    pub fn draw_image(&mut self, image: &Image, x: f64, y: f64, width: f64, height: f64) {
+        let (image_width, image_height) = match image.aspect_ratio() {
+            ratio if ratio > width / height => (width, width / ratio),
+            ratio => (height * ratio, height),
         };
-        let current_layer = self.get_current_layer();
-        current_layer.add_shape(square);
+        let [top_left, _, bottom_right, _] = self.rectangle(x + (width - image_width) / 2.0, y + (height - image_height) / 2.0, image_width, image_height);
+        self.backend.image(image, top_left.0, top_left.1, bottom_right.0 - top_left.0, bottom_right.1 - top_left.1);
+    }
+
+    /// Draws a QR code from its dark modules, row by row. It is placed like other shapes on mirrored
+    /// scorecards, but the code itself is never mirrored, as scanners can not read that.
+    pub fn draw_qr_code(&mut self, mut x: f64, y: f64, size: f64, width: usize, dark: &[bool]) {
+        if self.right_to_left {
+            x = self.card_size.0 - x - size;
+        }
+        let module = size / width as f64;
+        for (row, modules) in dark.chunks(width).enumerate() {
+            let y = y + row as f64 * module;
+            let mut column = 0;
+            //Neighbouring dark modules are drawn as one rectangle, which keeps the pdf small.
+            while column < width {
+                if !modules[column] {
+                    column += 1;
+                    continue;
+                }
+                let start = column;
+                while column < width && modules[column] {
+                    column += 1;
+                }
+                let (x1, x2) = (x + start as f64 * module, x + column as f64 * module);
+                let points = [self.point(x1, y), self.point(x2, y), self.point(x2, y + module), self.point(x1, y + module)];
+                self.backend.fill(&points, (0.0, 0.0, 0.0));
+            }
+        }
+    }
+
+    /// Straight line with the given thickness in pt.
+    pub fn draw_line(&mut self, mut x1: f64, y1: f64, mut x2: f64, y2: f64, thickness: f64) {
+        if self.right_to_left {
+            x1 = self.card_size.0 - x1;
+            x2 = self.card_size.0 - x2;
+        }
+        let points = [self.point(x1, y1), self.point(x2, y2)];
+        self.backend.stroke(&points, false, thickness * self.scale, None);
     }
 
-    pub fn write_multi_text(&mut self, mut x: f64, y: f64, font_size: f64, alignemnt: Alignment, strings: &[(&str, Weight)]) {
-        let width_of_string = strings.iter().map(|(string, weight)| match weight {
-            Weight::Normal => get_width_of_string(&self.normal_font_width, string, font_size),
-            Weight::Bold => get_width_of_string(&self.bold_font_width, string, font_size),
-        }).sum();
+    pub fn write_multi_text(&mut self, mut x: f64, y: f64, font_size: f64, mut alignemnt: Alignment, strings: &[(&str, Weight)]) {
+        //Text is written left to right in visual order, so on a mirrored scorecard the parts are
+        //written in reverse and each part is reordered on its own.
+        let mut strings: Vec<(String, Weight)> = strings.iter()
+            .map(|(string, weight)| (visual_order(string, self.right_to_left), *weight))
+            .collect();
+        if self.right_to_left {
+            x = self.card_size.0 - x;
+            alignemnt = match alignemnt {
+                Alignment::Left => Alignment::Right,
+                Alignment::Right => Alignment::Left,
+                Alignment::Center => Alignment::Center,
+            };
+            strings.reverse();
+        }
+        let width_of_string: f64 = strings.iter().map(|(string, weight)| self.width_of_visual_string(string, font_size, *weight)).sum();
         x -= match alignemnt {
             Alignment::Left => 0.0,
             Alignment::Right => width_of_string,
             Alignment::Center => width_of_string / 2.0,
         };
-        let current_layer = self.get_current_layer();
-        current_layer.begin_text_section();
-        current_layer.set_text_cursor(Mm(x + self.offset_x), Mm(self.offset_y - y));
-        current_layer.set_line_height(12.0);
-        for (string, weight) in strings {
-            let font = match weight {
-                Weight::Normal => &self.normal_font,
-                Weight::Bold => &self.bold_font,
-            };
-            current_layer.set_font(font, font_size);
-            current_layer.write_text(*string, font);
-        }
-        current_layer.end_text_section();
-
+        //Characters missing in a font are written with the next font which has them.
+        let runs: Vec<TextRun> = strings.iter()
+            .flat_map(|(string, weight)| {
+                let fonts = self.fonts.stack(*weight);
+                fonts.runs(string).into_iter()
+                    .map(move |(face, text)| TextRun { text, weight: *weight, face, font: fonts.bytes(face) })
+            })
+            .collect();
+        let (x, y) = self.point(x, y);
+        self.backend.text(x, y, font_size * self.scale, &runs);
     }
 
     pub fn write(&mut self, string: &str, x: f64, y: f64, font_size: f64, alignemnt: Alignment, weight: Weight) {
         self.write_multi_text(x, y, font_size, alignemnt, &[(string, weight)]);
     }
 
+    /// Writes the text like `write_multi_text`, but keeps it within the width of the fit. Returns
+    /// whether any of it had to be cut off.
+    pub fn write_fitted(&mut self, x: f64, y: f64, font_size: f64, alignment: Alignment, strings: &[(&str, Weight)], fit: TextFit) -> bool {
+        let size = self.fitted_size(strings, font_size, fit);
+        let max_lines = match fit.overflow {
+            Overflow::Ellipsis => 1,
+            Overflow::Wrap(lines) => lines.max(1),
+        };
+        let (lines, cut) = self.break_lines(strings, size, fit.max_width, max_lines);
+        for (idx, line) in lines.iter().enumerate() {
+            let parts: Vec<(&str, Weight)> = line.iter().map(|(string, weight)| (string.as_str(), *weight)).collect();
+            self.write_multi_text(x, y + idx as f64 * size * LINE_SPACING, size, alignment, &parts);
+        }
+        cut
+    }
+
+    /// The size `write_fitted` writes the text in, e.g. to place it before it is written. Text is
+    /// shrunk from the font size down to the minimum size of the fit until it fits its width.
+    pub fn fitted_size(&self, strings: &[(&str, Weight)], font_size: f64, fit: TextFit) -> f64 {
+        let mut size = font_size;
+        while size > fit.min_size && self.width_of_parts(strings, size) > fit.max_width {
+            size = (size - 0.5).max(fit.min_size);
+        }
+        size
+    }
+
+    /// Breaks the text into lines between words, and cuts off what does not fit on the lines.
+    fn break_lines(&self, strings: &[(&str, Weight)], size: f64, max_width: f64, max_lines: usize) -> (Vec<Vec<(String, Weight)>>, bool) {
+        let mut lines: Vec<Vec<(String, Weight)>> = vec![vec![]];
+        let mut width = 0.0;
+        let mut cut = false;
+        'words: for (string, weight) in strings {
+            for word in string.split_inclusive(' ') {
+                let line_width = width + self.get_width_of_string(word.trim_end(), size, *weight);
+                if line_width > max_width && width > 0.0 {
+                    if lines.len() == max_lines {
+                        cut = true;
+                        break 'words;
+                    }
+                    trim_line(lines.last_mut().unwrap());
+                    lines.push(vec![]);
+                    width = 0.0;
+                }
+                let line = lines.last_mut().unwrap();
+                match line.last_mut() {
+                    Some((text, last)) if last == weight => text.push_str(word),
+                    _ => line.push((word.to_string(), *weight)),
+                }
+                width += self.get_width_of_string(word, size, *weight);
+            }
+        }
+        //Words longer than a whole line are cut off as well.
+        for idx in 0..lines.len() {
+            let last = idx + 1 == lines.len();
+            trim_line(&mut lines[idx]);
+            if (last && cut) || self.width_of_line(&lines[idx], size) > max_width {
+                self.ellipsize(&mut lines[idx], size, max_width);
+                cut = true;
+            }
+        }
+        (lines, cut)
+    }
+
+    /// Removes characters from the end until the line fits with an ellipsis.
+    fn ellipsize(&self, line: &mut Vec<(String, Weight)>, size: f64, max_width: f64) {
+        loop {
+            trim_line(line);
+            let weight = line.last().map_or(Weight::Normal, |(_, weight)| *weight);
+            let width = self.width_of_line(line, size) + self.get_width_of_string(ELLIPSIS, size, weight);
+            match line.last_mut() {
+                Some((text, _)) if width > max_width => {
+                    text.pop();
+                }
+                Some((text, _)) => {
+                    text.push_str(ELLIPSIS);
+                    return;
+                }
+                None => return,
+            }
+        }
+    }
+
+    fn width_of_parts(&self, strings: &[(&str, Weight)], size: f64) -> f64 {
+        strings.iter().map(|(string, weight)| self.get_width_of_string(string, size, *weight)).sum()
+    }
+
+    fn width_of_line(&self, line: &[(String, Weight)], size: f64) -> f64 {
+        line.iter().map(|(string, weight)| self.get_width_of_string(string, size, *weight)).sum()
+    }
+
+    /// Converts coordinates in mm from the top left corner of the scorecard to the page.
+    fn point(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.offset_x + x * self.scale, self.offset_y + y * self.scale)
+    }
+
     pub fn get_competition_name(&self) -> &str {
-        &self.competition_name
+        self.competition_name
     }
 
-    pub fn doc(self) -> PdfDocumentReference {
-        self.document
+    /// What has been drawn, e.g. to get the pages of an svg backend.
+    pub(crate) fn into_backend(self) -> B {
+        self.backend
     }
 
     pub fn get_width_of_string(&self, string: &str, font_size: f64, weight: Weight) -> f64 {
-        match weight {
-            Weight::Normal => get_width_of_string(&self.normal_font_width, string, font_size),
-            Weight::Bold => get_width_of_string(&self.bold_font_width, string, font_size),
+        self.width_of_visual_string(&visual_order(string, self.right_to_left), font_size, weight)
+    }
+
+    /// Shaping changes which glyphs are used, so widths are measured on the text as it is written.
+    fn width_of_visual_string(&self, string: &str, font_size: f64, weight: Weight) -> f64 {
+        self.fonts.stack(weight).width(string, font_size)
+    }
+}
+
+/// Removes trailing spaces and parts left empty.
+fn trim_line(line: &mut Vec<(String, Weight)>) {
+    while let Some((text, _)) = line.last_mut() {
+        text.truncate(text.trim_end().len());
+        if !text.is_empty() {
+            return;
         }
+        line.pop();
     }
 }
 
-pub fn get_width_of_string(font: &Font, string: &str, font_size: f64) -> f64 {
-    let upem = font.metrics().units_per_em;
-    let mut width = 0.0;
-    for char in string.chars() {
-        if !char.is_whitespace() {
-            if let Some(id) = font.glyph_for_char(char) {
-                let glyph_width = font.advance(id).unwrap().x();
-                width += glyph_width
-            }
-        } else {
-            width += upem as f32 / 4.0;
+/// Lines along which the pages are cut, in mm from the top left corner of the page.
+fn cut_lines(layout: &PageLayout) -> Vec<[(f64, f64); 2]> {
+    let (width, height) = layout.paper.dimensions();
+    let (cell_width, cell_height) = layout.cell_size();
+    let xs: Vec<_> = (0..=layout.columns).map(|c| layout.margins.left + c as f64 * cell_width).collect();
+    let ys: Vec<_> = (0..=layout.rows).map(|r| layout.margins.top + r as f64 * cell_height).collect();
+    let line = |x1: f64, y1: f64, x2: f64, y2: f64| [(x1, y1), (x2, y2)];
+    match layout.cut_marks {
+        CutMarks::None => vec![],
+        //Lines on the edge of the paper are only needed if there is a margin to cut off.
+        CutMarks::Dashed => {
+            let vertical = xs.iter().filter(|x| **x > 0.0 && **x < width).map(|x| line(*x, 0.0, *x, height));
+            let horizontal = ys.iter().filter(|y| **y > 0.0 && **y < height).map(|y| line(0.0, *y, width, *y));
+            vertical.chain(horizontal).collect()
+        }
+        CutMarks::Corners => {
+            let arm = 3.0;
+            xs.iter().flat_map(|x| ys.iter().map(move |y| (*x, *y)))
+                .flat_map(|(x, y)| [line(x - arm, y, x + arm, y), line(x, y - arm, x, y + arm)])
+                .collect()
         }
     }
-    (width as f64 / (upem as f64 / font_size)) / 2.83
 }
 
-fn line_from_points(points: Vec<(Point, bool)>) -> Line {
-    Line {
-        points,
-        is_closed: false,
-        has_fill: false,
-        has_stroke: true,
-        is_clipping_path: false,
+#[cfg(test)]
+mod test {
+    use crate::backend::SvgBackend;
+    use crate::font::Fonts;
+    use crate::page_layout::{PageLayout, CARD_HEIGHT, CARD_WIDTH};
+    use super::{Overflow, ScorecardGenerator, TextFit, Weight, ELLIPSIS};
+    use Weight::*;
+
+    //Widths are taken from the fonts, so the tests only compare them with each other.
+    fn generator() -> ScorecardGenerator<'static, SvgBackend> {
+        let mut fonts = Fonts::new();
+        fonts.set_system_fallback(false);
+        ScorecardGenerator::with_backend(SvgBackend::new(), "", &PageLayout::a4(), &fonts.load(), false, (CARD_WIDTH, CARD_HEIGHT))
+    }
+
+    fn line(parts: &[(&str, Weight)]) -> Vec<(String, Weight)> {
+        parts.iter().map(|(text, weight)| (text.to_string(), *weight)).collect()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn break_lines() {
+        let generator = generator();
+        let width = |text| generator.get_width_of_string(text, 10.0, Normal) + 1e-6;
+        let (lines, cut) = generator.break_lines(&[("Ana Smith", Normal)], 10.0, width("Ana Smith"), 1);
+        assert_eq!((lines, cut), (vec![line(&[("Ana Smith", Normal)])], false));
+        let (lines, cut) = generator.break_lines(&[("Ana Smith Lee", Normal)], 10.0, width("Ana Smith"), 2);
+        assert_eq!((lines, cut), (vec![line(&[("Ana Smith", Normal)]), line(&[("Lee", Normal)])], false));
+        //Parts of the same weight are joined, and words stay together across parts.
+        let (lines, cut) = generator.break_lines(&[("Round ", Normal), ("1 ", Normal), ("3x3", Bold)], 10.0, 100.0, 1);
+        assert_eq!((lines, cut), (vec![line(&[("Round 1 ", Normal), ("3x3", Bold)])], false));
+    }
+
+    #[test]
+    fn cut_off_lines() {
+        let generator = generator();
+        let width = |text| generator.get_width_of_string(text, 10.0, Normal) + 1e-6;
+        let (lines, cut) = generator.break_lines(&[("Ana Smith Lee", Normal)], 10.0, width("Ana Smith"), 1);
+        assert!(cut);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0][0].0.ends_with(ELLIPSIS));
+        assert!(generator.width_of_line(&lines[0], 10.0) <= width("Ana Smith"));
+        //A word longer than the line is cut off, even if there are lines left.
+        let (lines, cut) = generator.break_lines(&[("Supercalifragilistic", Normal)], 10.0, width("Super..."), 3);
+        assert_eq!((lines, cut), (vec![line(&[("Super...", Normal)])], true));
+    }
+
+    #[test]
+    fn ellipsize() {
+        let generator = generator();
+        let width = |text| generator.get_width_of_string(text, 10.0, Normal) + 1e-6;
+        let mut text = line(&[("Ana Smith", Normal)]);
+        generator.ellipsize(&mut text, 10.0, width("Ana..."));
+        //Spaces before the ellipsis are removed.
+        assert_eq!(text, line(&[("Ana...", Normal)]));
+        let mut text = line(&[("Round ", Normal), ("3x3", Bold)]);
+        generator.ellipsize(&mut text, 10.0, width("Round..."));
+        assert_eq!(text, line(&[("Round...", Normal)]));
+        let mut text = line(&[("Ana", Normal)]);
+        generator.ellipsize(&mut text, 10.0, 0.0);
+        assert_eq!(text, vec![]);
+    }
+
+    #[test]
+    fn fitted_size() {
+        let generator = generator();
+        let fit = TextFit { max_width: generator.get_width_of_string("Ana Smith", 10.0, Normal) + 1e-6, min_size: 4.0, overflow: Overflow::Ellipsis };
+        assert_eq!(generator.fitted_size(&[("Ana", Normal)], 10.0, fit), 10.0);
+        assert_eq!(generator.fitted_size(&[("Ana Smith", Normal)], 10.0, fit), 10.0);
+        //Bold text is wider, so it is shrunk further.
+        let bold = generator.fitted_size(&[("Ana Smith", Bold)], 10.0, fit);
+        assert!(bold < 10.0 && generator.get_width_of_string("Ana Smith", bold, Bold) <= fit.max_width);
+        assert_eq!(generator.fitted_size(&[(&"Ana Smith ".repeat(4), Normal)], 10.0, fit), 4.0);
+    }
+}
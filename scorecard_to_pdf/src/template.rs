@@ -0,0 +1,433 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::scorecard_generator::{Alignment, Weight, Overflow, TextFit};
+
+/// Payload of QR codes unless a template sets another one. Blank fields are left empty.
+pub const DEFAULT_QR_PAYLOAD: &str = "{competition_id};{id};{event_id};{round};{group};{station}";
+
+/// Declarative description of a scorecard. All coordinates are in mm from the top left corner of
+/// the scorecard, which is 105 mm wide and 99 mm high.
+///
+/// Text can contain placeholders which are replaced for every scorecard: `{competition}`,
+/// `{competition_id}`, `{event}`, `{event_id}`, `{round}`, `{group}`, `{id}`, `{name}`, `{station}`,
+/// `{stage}`, `{limit}`, `{mbf_limit}` for the multi-blind time limit, which depends on the number
+/// of cubes, `{attempt}` for the attempt of a sheet and `{lang.<key>}` for the labels of the
+/// `Language`, e.g. `{lang.judge}`. `{event}` is the translated name of the event and `{event_id}`
+/// its wcif id, e.g. `333`. QR code payloads can contain them as well.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScorecardTemplate {
+    /// Prints a sheet for every attempt instead of one for the round, e.g. for fewest moves where
+    /// every solution is written on its own sheet. The attempt rows then only have the row of the
+    /// attempt of the sheet.
+    #[serde(default)]
+    pub sheet_per_attempt: bool,
+    pub elements: Vec<Element>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Text(TextElement),
+    Box(BoxElement),
+    AttemptRows(AttemptRows),
+    Grid(GridElement),
+    /// QR code for data entry. It is left out on blank scorecards.
+    QrCode(QrCodeElement),
+    /// The logo of the competition, as large as it fits in the box. Left empty when no logo is given.
+    Logo(BoxElement),
+    /// One of the named images of the options, e.g. the logo of a sponsor.
+    Image(ImageElement),
+    /// Filled with the colour of the stage, so stacks of different stages are easy to tell apart.
+    /// Left empty for stages without a colour.
+    StageStripe(BoxElement),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextElement {
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+    #[serde(default)]
+    pub align: Alignment,
+    /// Text wider than this is shrunk down to `min_size`, and then wrapped or cut off as given by
+    /// `overflow`.
+    #[serde(default)]
+    pub max_width: Option<f64>,
+    /// Smallest font size to shrink to. Two thirds of the size if not given.
+    #[serde(default)]
+    pub min_size: Option<f64>,
+    #[serde(default)]
+    pub overflow: Overflow,
+    pub parts: Vec<TextPart>,
+}
+
+impl TextElement {
+    /// How the text is kept within its width, if it has one.
+    pub(crate) fn fit(&self) -> Option<TextFit> {
+        self.max_width.map(|max_width| TextFit {
+            max_width,
+            min_size: self.min_size.unwrap_or(self.size * 2.0 / 3.0),
+            overflow: self.overflow,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextPart {
+    pub text: String,
+    #[serde(default)]
+    pub weight: Weight,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BoxElement {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ImageElement {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Name of the image in the options. Unknown names are left empty.
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QrCodeElement {
+    pub x: f64,
+    pub y: f64,
+    /// Width and height of the code. Scanners need a few mm of white space around it as well.
+    pub size: f64,
+    #[serde(default = "default_qr_payload")]
+    pub payload: String,
+}
+
+fn default_qr_payload() -> String {
+    DEFAULT_QR_PAYLOAD.to_string()
+}
+
+/// Boxes to write one move in each, as on a fewest moves solution sheet.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GridElement {
+    pub x: f64,
+    pub y: f64,
+    pub rows: usize,
+    pub columns: usize,
+    pub cell_width: f64,
+    pub cell_height: f64,
+    /// Font size of the running count written to the right of every row. No count without it.
+    #[serde(default)]
+    pub count_size: Option<f64>,
+}
+
+/// One row per attempt of the round, made of a box per column and the attempt number to the left of
+/// the row. If the round has a cutoff, a line is drawn below the last attempt before it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AttemptRows {
+    /// Left edge of the first column.
+    pub x: f64,
+    /// Top of the first row. Column headers are written just above.
+    pub y: f64,
+    pub height: f64,
+    /// Distance from the top of one row to the top of the next.
+    pub distance: f64,
+    pub number_x: f64,
+    pub number_size: f64,
+    pub header_size: f64,
+    pub columns: Vec<AttemptColumn>,
+    #[serde(default)]
+    pub extra: Option<ExtraRows>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AttemptColumn {
+    pub width: f64,
+    pub header: String,
+}
+
+/// Rows for extra attempts, placed below the attempts and numbered with `_`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExtraRows {
+    pub count: usize,
+    /// Space between the last attempt and the first extra row.
+    pub gap: f64,
+    pub header: String,
+    pub header_x: f64,
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    UnknownFormat(String),
+    Invalid(String),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::Io(e) => write!(f, "Unable to read scorecard template: {e}"),
+            TemplateError::Json(e) => write!(f, "Malformed json scorecard template: {e}"),
+            TemplateError::Toml(e) => write!(f, "Malformed toml scorecard template: {e}"),
+            TemplateError::UnknownFormat(ext) => write!(f, "Scorecard templates must be .json or .toml files, not \"{ext}\""),
+            TemplateError::Invalid(reason) => write!(f, "Invalid scorecard template: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl ScorecardTemplate {
+    pub fn from_json(json: &str) -> Result<ScorecardTemplate, TemplateError> {
+        let template: ScorecardTemplate = serde_json::from_str(json).map_err(TemplateError::Json)?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<ScorecardTemplate, TemplateError> {
+        let template: ScorecardTemplate = toml::from_str(toml).map_err(TemplateError::Toml)?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    /// Loads a template from a .json or .toml file.
+    pub fn load(path: impl AsRef<Path>) -> Result<ScorecardTemplate, TemplateError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(TemplateError::Io)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => ScorecardTemplate::from_json(&content),
+            Some("toml") => ScorecardTemplate::from_toml(&content),
+            ext => Err(TemplateError::UnknownFormat(ext.unwrap_or("").to_string()))
+        }
+    }
+
+    /// The template as pretty printed json, e.g. to use the default design as a starting point.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    fn validate(&self) -> Result<(), TemplateError> {
+        for element in &self.elements {
+            match element {
+                Element::AttemptRows(rows) if rows.columns.is_empty() => return Err(TemplateError::Invalid("Attempt rows without columns".to_string())),
+                Element::Text(text) if text.size <= 0.0 => return Err(TemplateError::Invalid("Text with a font size of zero or less".to_string())),
+                _ => ()
+            }
+        }
+        Ok(())
+    }
+}
+
+fn text(x: f64, y: f64, size: f64, align: Alignment, parts: &[(&str, Weight)]) -> TextElement {
+    TextElement {
+        x,
+        y,
+        size,
+        align,
+        max_width: None,
+        min_size: None,
+        overflow: Overflow::Ellipsis,
+        parts: parts.iter().map(|(text, weight)| TextPart { text: text.to_string(), weight: *weight }).collect(),
+    }
+}
+
+fn square(x: f64, y: f64, width: f64, height: f64) -> Element {
+    Element::Box(BoxElement { x, y, width, height })
+}
+
+fn column(width: f64, header: &str) -> AttemptColumn {
+    AttemptColumn { width, header: header.to_string() }
+}
+
+/// Competition, round, competitor and station, which are the same on every kind of scorecard.
+fn header() -> Vec<Element> {
+    use Alignment::*;
+    use Weight::*;
+    vec![
+        Element::Logo(BoxElement { x: 16.0, y: 1.0, width: 10.0, height: 7.5 }),
+        Element::Text(TextElement { max_width: Some(51.0), ..text(52.5, 7.0, 10.0, Center, &[("{competition}", Normal)]) }),
+        Element::Text(TextElement { max_width: Some(64.0), ..text(52.5, 11.5, 10.0, Center, &[
+            ("{lang.round}: {round} | ", Normal),
+            ("{event}", Bold),
+            (" | {lang.group}: {group}", Normal),
+        ]) }),
+        square(5.0, 15.0, 10.0, 5.5),
+        Element::Text(TextElement { max_width: Some(9.5), ..text(10.0, 19.0, 10.0, Center, &[("{id}", Normal)]) }),
+        square(15.0, 15.0, 85.0, 5.5),
+        Element::Text(TextElement { max_width: Some(83.0), ..text(16.0, 19.0, 10.0, Left, &[("{name}", Normal)]) }),
+        Element::Text(TextElement { max_width: Some(15.0), ..text(100.0, 12.0, 20.0, Right, &[("{station}", Bold)]) }),
+        Element::Text(TextElement { max_width: Some(30.0), ..text(100.0, 4.0, 7.0, Right, &[("{stage}", Bold)]) }),
+        Element::StageStripe(BoxElement { x: 101.5, y: 3.0, width: 2.0, height: 93.0 }),
+        Element::QrCode(QrCodeElement { x: 3.0, y: 2.0, size: 11.0, payload: default_qr_payload() }),
+    ]
+}
+
+fn attempt_rows(columns: Vec<AttemptColumn>, extra: Option<ExtraRows>) -> Element {
+    Element::AttemptRows(AttemptRows {
+        x: 9.0,
+        y: 25.5,
+        height: 8.2,
+        distance: 8.8,
+        number_x: 5.0,
+        number_size: 12.0,
+        header_size: 7.0,
+        columns,
+        extra,
+    })
+}
+
+fn extra_attempts(count: usize) -> Option<ExtraRows> {
+    Some(ExtraRows {
+        count,
+        gap: 3.8,
+        header: "{lang.extra_attempts}".to_string(),
+        header_x: 52.5,
+    })
+}
+
+impl ScorecardTemplate {
+    /// Solution sheet for fewest moves with a box for every move and a line for the signature of
+    /// the competitor.
+    pub fn fewest_moves() -> ScorecardTemplate {
+        use Alignment::*;
+        use Weight::*;
+        let mut elements = header();
+        elements.extend([
+            attempt_rows(vec![
+                column(71.0, "{lang.moves}"),
+                column(10.0, "{lang.judge}"),
+                column(10.0, "{lang.comp}"),
+            ], None),
+            Element::Text(text(5.0, 57.0, 7.0, Left, &[("{lang.solution}", Normal), (" | {lang.attempt} {attempt}", Bold)])),
+            Element::Grid(GridElement {
+                x: 5.0,
+                y: 58.0,
+                rows: 4,
+                columns: 20,
+                cell_width: 4.5,
+                cell_height: 5.0,
+                count_size: Some(5.0),
+            }),
+            Element::Text(text(5.0, 86.5, 7.0, Left, &[("{lang.signature}", Normal)])),
+            square(35.0, 81.5, 65.0, 6.5),
+            Element::Text(TextElement { max_width: Some(95.0), min_size: Some(5.0), ..text(100.0, 94.0, 7.0, Right, &[("{limit}", Normal)]) }),
+        ]);
+        ScorecardTemplate { sheet_per_attempt: true, elements }
+    }
+
+    /// Scorecard for multi-blind with the number of cubes attempted and solved and the time of every
+    /// attempt. The time limit depends on the number of cubes, so the rule for it is printed instead.
+    pub fn multi_blind() -> ScorecardTemplate {
+        use Alignment::*;
+        use Weight::*;
+        let mut elements = header();
+        elements.extend([
+            attempt_rows(vec![
+                column(10.0, "{lang.scram}"),
+                column(16.0, "{lang.attempted}"),
+                column(16.0, "{lang.solved}"),
+                column(29.0, "{lang.time}"),
+                column(10.0, "{lang.judge}"),
+                column(10.0, "{lang.comp}"),
+            ], extra_attempts(1)),
+            Element::Text(TextElement { max_width: Some(95.0), min_size: Some(5.0), ..text(100.0, 94.0, 7.0, Right, &[("{mbf_limit}", Normal)]) }),
+        ]);
+        ScorecardTemplate { sheet_per_attempt: false, elements }
+    }
+}
+
+impl Default for ScorecardTemplate {
+    fn default() -> Self {
+        use Alignment::*;
+        use Weight::*;
+        let sign_box_width = 10.0;
+        let mut elements = header();
+        elements.extend([
+            attempt_rows(vec![
+                column(sign_box_width, "{lang.scram}"),
+                column(91.0 - 3.0 * sign_box_width, "{lang.result}"),
+                column(sign_box_width, "{lang.judge}"),
+                column(sign_box_width, "{lang.comp}"),
+            ], extra_attempts(2)),
+            Element::Text(TextElement { max_width: Some(95.0), min_size: Some(5.0), ..text(100.0, 94.0, 7.0, Right, &[("{limit}", Normal)]) }),
+        ]);
+        ScorecardTemplate { sheet_per_attempt: false, elements }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scorecard_generator::{Alignment, Overflow, TextFit, Weight};
+    use super::{Element, ScorecardTemplate, TemplateError};
+
+    fn builtin() -> [ScorecardTemplate; 3] {
+        [ScorecardTemplate::default(), ScorecardTemplate::fewest_moves(), ScorecardTemplate::multi_blind()]
+    }
+
+    #[test]
+    fn json_round_trip() {
+        for template in builtin() {
+            assert_eq!(ScorecardTemplate::from_json(&template.to_json()).unwrap(), template);
+        }
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        for template in builtin() {
+            let toml = toml::to_string(&template).unwrap();
+            assert_eq!(ScorecardTemplate::from_toml(&toml).unwrap(), template);
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let template = ScorecardTemplate::from_toml(r#"
+            [[elements]]
+            type = "text"
+            x = 10.0
+            y = 20.0
+            size = 8.0
+            parts = [{ text = "{name}" }]
+
+            [[elements]]
+            type = "text"
+            x = 10.0
+            y = 30.0
+            size = 8.0
+            align = "right"
+            max_width = 50.0
+            overflow = { wrap = 2 }
+            parts = [{ text = "{limit}", weight = "bold" }]
+        "#).unwrap();
+        match &template.elements[..] {
+            [Element::Text(text), Element::Text(limited)] => {
+                assert_eq!(text.align, Alignment::default());
+                assert_eq!(text.overflow, Overflow::default());
+                assert_eq!(text.parts[0].weight, Weight::Normal);
+                assert_eq!(text.fit(), None);
+                assert_eq!(limited.align, Alignment::Right);
+                assert_eq!(limited.parts[0].weight, Weight::Bold);
+                assert_eq!(limited.fit(), Some(TextFit { max_width: 50.0, min_size: 8.0 * 2.0 / 3.0, overflow: Overflow::Wrap(2) }));
+            }
+            elements => panic!("Unexpected elements {elements:?}")
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let no_columns = r#"{ "elements": [{ "type": "attempt_rows", "x": 9, "y": 25, "height": 8, "distance": 9,
+            "number_x": 5, "number_size": 12, "header_size": 7, "columns": [] }] }"#;
+        assert!(matches!(ScorecardTemplate::from_json(no_columns), Err(TemplateError::Invalid(_))));
+        let no_size = r#"{ "elements": [{ "type": "text", "x": 0, "y": 0, "size": 0, "parts": [] }] }"#;
+        assert!(matches!(ScorecardTemplate::from_json(no_size), Err(TemplateError::Invalid(_))));
+        assert!(matches!(ScorecardTemplate::from_json(r#"{ "elements": [{ "type": "circle" }] }"#), Err(TemplateError::Json(_))));
+        assert!(matches!(ScorecardTemplate::from_toml("elements = 3"), Err(TemplateError::Toml(_))));
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use crate::*;
+
+/// Replaces personal data in a wcif with pseudonyms while keeping everything needed to reproduce
+/// grouping and scorecard behaviour (registrations, assignments, personal bests and results).
+/// The same person (by wca user id) always gets the same pseudonym, also across several wcifs
+/// anonymized with the same `Anonymizer`, so snapshots of one competition stay comparable.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    pseudonyms: HashMap<usize, usize>
+}
+
+impl Anonymizer {
+    pub fn new() -> Anonymizer {
+        Anonymizer::default()
+    }
+
+    pub fn anonymize(&mut self, wcif: &mut Wcif) {
+        for person in wcif.persons.iter_mut() {
+            self.anonymize_person(person);
+        }
+    }
+
+    pub fn anonymize_person(&mut self, person: &mut Person) {
+        let next = self.pseudonyms.len() + 1;
+        let no = *self.pseudonyms.entry(person.wca_user_id).or_insert(next);
+        person.name = format!("Competitor {no}");
+        person.email = format!("competitor{no}@example.com");
+        person.wca_user_id = no;
+        //The year is kept so ages stay roughly the same, as the grouping depends on them.
+        person.birthdate = Date::from_ymd_opt(person.birthdate.year(), 1, 1).unwrap();
+        person.avatar = None;
+        person.wca_id = person.wca_id.as_ref().map(|wca_id| WcaId {
+            year: wca_id.year,
+            chars: pseudonym_chars(no / 100),
+            id: (no % 100) as u8
+        });
+        if let Some(registration) = person.registration.as_mut() {
+            registration.comments = String::new();
+        }
+    }
+}
+
+impl Wcif {
+    pub fn anonymize(&mut self) {
+        Anonymizer::new().anonymize(self)
+    }
+}
+
+pub(crate) fn pseudonym_chars(mut n: usize) -> [u8; 4] {
+    let mut chars = *b"AAAA";
+    for c in chars.iter_mut().rev() {
+        *c += (n % 26) as u8;
+        n /= 26;
+    }
+    chars
+}
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+
+use crate::*;
+use crate::oauth::ActivityIter;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WcifDiff<'a> {
+    pub persons_added: Vec<PersonRef<'a>>,
+    pub persons_removed: Vec<PersonRef<'a>>,
+    pub assignments_changed: Vec<AssignmentChange<'a>>,
+    pub activities_added: Vec<ActivityRef<'a>>,
+    pub activities_removed: Vec<ActivityRef<'a>>,
+    pub activities_moved: Vec<ActivityMove<'a>>,
+    pub rounds_changed: Vec<RoundChange<'a>>,
+    pub extensions_changed: Vec<ExtensionChange<'a>>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonRef<'a> {
+    pub wca_user_id: usize,
+    pub registrant_id: Option<usize>,
+    pub name: &'a str,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssignmentChange<'a> {
+    pub person: PersonRef<'a>,
+    pub added: Vec<&'a Assignment>,
+    pub removed: Vec<&'a Assignment>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityRef<'a> {
+    pub id: usize,
+    pub activity_code: &'a str,
+    pub room: &'a str,
+    pub start_time: String,
+    pub end_time: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityMove<'a> {
+    pub old: ActivityRef<'a>,
+    pub new: ActivityRef<'a>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundChange<'a> {
+    pub round_id: &'a str,
+    pub field: RoundField<'a>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "field", content = "change", rename_all = "camelCase")]
+pub enum RoundField<'a> {
+    Added,
+    Removed,
+    Format(Change<&'a char>),
+    TimeLimit(Change<Option<&'a TimeLimit>>),
+    Cutoff(Change<Option<&'a Cutoff>>),
+    AdvancementCondition(Change<Option<&'a AdvancementCondition>>),
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionChange<'a> {
+    /// Where the extension lives, e.g. `competition`, `event 333` or `activity 12`.
+    pub location: String,
+    pub id: String,
+    pub old: Option<&'a serde_json::Value>,
+    pub new: Option<&'a serde_json::Value>,
+}
+
+impl<'a> WcifDiff<'a> {
+    pub fn new(old: &'a Wcif, new: &'a Wcif) -> WcifDiff<'a> {
+        let mut diff = WcifDiff {
+            persons_added: vec![],
+            persons_removed: vec![],
+            assignments_changed: vec![],
+            activities_added: vec![],
+            activities_removed: vec![],
+            activities_moved: vec![],
+            rounds_changed: vec![],
+            extensions_changed: vec![],
+        };
+        diff.persons(old, new);
+        diff.activities(old, new);
+        diff.rounds(old, new);
+        diff.extensions(old, new);
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.persons_added.is_empty() &&
+        self.persons_removed.is_empty() &&
+        self.assignments_changed.is_empty() &&
+        self.activities_added.is_empty() &&
+        self.activities_removed.is_empty() &&
+        self.activities_moved.is_empty() &&
+        self.rounds_changed.is_empty() &&
+        self.extensions_changed.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    fn persons(&mut self, old: &'a Wcif, new: &'a Wcif) {
+        let old_map: HashMap<_, _> = old.persons.iter().map(|p| (p.wca_user_id, p)).collect();
+        let new_map: HashMap<_, _> = new.persons.iter().map(|p| (p.wca_user_id, p)).collect();
+        for person in &new.persons {
+            match old_map.get(&person.wca_user_id) {
+                None => self.persons_added.push(PersonRef::new(person)),
+                Some(old_person) => {
+                    let added: Vec<_> = person.assignments.iter().filter(|a| !old_person.assignments.contains(a)).collect();
+                    let removed: Vec<_> = old_person.assignments.iter().filter(|a| !person.assignments.contains(a)).collect();
+                    if !added.is_empty() || !removed.is_empty() {
+                        self.assignments_changed.push(AssignmentChange { person: PersonRef::new(person), added, removed });
+                    }
+                }
+            }
+        }
+        self.persons_removed = old.persons.iter()
+            .filter(|p| !new_map.contains_key(&p.wca_user_id))
+            .map(PersonRef::new)
+            .collect();
+    }
+
+    fn activities(&mut self, old: &'a Wcif, new: &'a Wcif) {
+        let old_map: HashMap<_, _> = activities_with_room(old).map(|(room, act)| (act.id, (room, act))).collect();
+        let new_map: HashMap<_, _> = activities_with_room(new).map(|(room, act)| (act.id, (room, act))).collect();
+        for (room, act) in activities_with_room(new) {
+            match old_map.get(&act.id) {
+                None => self.activities_added.push(ActivityRef::new(room, act)),
+                Some((old_room, old_act)) => {
+                    if old_room.id != room.id || old_act.start_time != act.start_time || old_act.end_time != act.end_time {
+                        self.activities_moved.push(ActivityMove { old: ActivityRef::new(old_room, old_act), new: ActivityRef::new(room, act) });
+                    }
+                }
+            }
+        }
+        self.activities_removed = activities_with_room(old)
+            .filter(|(_, act)| !new_map.contains_key(&act.id))
+            .map(|(room, act)| ActivityRef::new(room, act))
+            .collect();
+    }
+
+    fn rounds(&mut self, old: &'a Wcif, new: &'a Wcif) {
+        let old_map: HashMap<_, _> = old.events.iter().flat_map(|e| &e.rounds).map(|r| (r.id.as_str(), r)).collect();
+        let new_map: HashMap<_, _> = new.events.iter().flat_map(|e| &e.rounds).map(|r| (r.id.as_str(), r)).collect();
+        for round in new.events.iter().flat_map(|e| &e.rounds) {
+            let round_id = round.id.as_str();
+            let old_round = match old_map.get(round_id) {
+                None => {
+                    self.rounds_changed.push(RoundChange { round_id, field: RoundField::Added });
+                    continue;
+                }
+                Some(v) => v
+            };
+            if old_round.format != round.format {
+                self.rounds_changed.push(RoundChange { round_id, field: RoundField::Format(Change { old: &old_round.format, new: &round.format }) });
+            }
+            if old_round.time_limit != round.time_limit {
+                self.rounds_changed.push(RoundChange { round_id, field: RoundField::TimeLimit(Change { old: old_round.time_limit.as_ref(), new: round.time_limit.as_ref() }) });
+            }
+            if old_round.cutoff != round.cutoff {
+                self.rounds_changed.push(RoundChange { round_id, field: RoundField::Cutoff(Change { old: old_round.cutoff.as_ref(), new: round.cutoff.as_ref() }) });
+            }
+            if old_round.advancement_condition != round.advancement_condition {
+                self.rounds_changed.push(RoundChange { round_id, field: RoundField::AdvancementCondition(Change { old: old_round.advancement_condition.as_ref(), new: round.advancement_condition.as_ref() }) });
+            }
+        }
+        for round in old.events.iter().flat_map(|e| &e.rounds) {
+            if !new_map.contains_key(round.id.as_str()) {
+                self.rounds_changed.push(RoundChange { round_id: &round.id, field: RoundField::Removed });
+            }
+        }
+    }
+
+    fn extensions(&mut self, old: &'a Wcif, new: &'a Wcif) {
+        let old_map: HashMap<_, _> = extensions_with_location(old).collect();
+        let new_map: HashMap<_, _> = extensions_with_location(new).collect();
+        for ((location, id), value) in extensions_with_location(new) {
+            let old_value = old_map.get(&(location.clone(), id.clone())).copied();
+            if old_value != Some(value) {
+                self.extensions_changed.push(ExtensionChange { location, id, old: old_value, new: Some(value) });
+            }
+        }
+        for ((location, id), value) in extensions_with_location(old) {
+            if !new_map.contains_key(&(location.clone(), id.clone())) {
+                self.extensions_changed.push(ExtensionChange { location, id, old: Some(value), new: None });
+            }
+        }
+    }
+}
+
+impl<'a> PersonRef<'a> {
+    fn new(person: &'a Person) -> PersonRef<'a> {
+        PersonRef { wca_user_id: person.wca_user_id, registrant_id: person.registrant_id, name: &person.name }
+    }
+}
+
+impl<'a> ActivityRef<'a> {
+    fn new(room: &'a Room, activity: &'a Activity) -> ActivityRef<'a> {
+        ActivityRef {
+            id: activity.id,
+            activity_code: &activity.activity_code,
+            room: &room.name,
+            start_time: activity.start_time.to_string(),
+            end_time: activity.end_time.to_string()
+        }
+    }
+}
+
+fn activities_with_room(wcif: &Wcif) -> impl Iterator<Item = (&Room, &Activity)> {
+    wcif.schedule.venues.iter()
+        .flat_map(|venue| &venue.rooms)
+        .flat_map(|room| std::iter::repeat(room).zip(ActivityIter::new(&room.activities)))
+}
+
+fn extensions_with_location(wcif: &Wcif) -> impl Iterator<Item = ((String, String), &serde_json::Value)> {
+    let competition = std::iter::once(("competition".to_string(), &wcif.extensions));
+    let events = wcif.events.iter().map(|e| (format!("event {}", e.id), &e.extensions));
+    let rounds = wcif.events.iter().flat_map(|e| &e.rounds).map(|r| (format!("round {}", r.id), &r.extensions));
+    let venues = wcif.schedule.venues.iter().map(|v| (format!("venue {}", v.id), &v.extensions));
+    let rooms = wcif.schedule.venues.iter().flat_map(|v| &v.rooms).map(|r| (format!("room {}", r.id), &r.extensions));
+    let activities = activities_with_room(wcif).map(|(_, a)| (format!("activity {}", a.id), &a.extensions));
+    competition
+        .chain(events)
+        .chain(rounds)
+        .chain(venues)
+        .chain(rooms)
+        .chain(activities)
+        .flat_map(|(location, extensions)| {
+            extensions.iter().enumerate().map(move |(idx, ext)| {
+                //Extensions are identified by their id field. Fall back to the position if it is missing.
+                let id = match ext.get("id").and_then(|id| id.as_str()) {
+                    Some(id) => id.to_string(),
+                    None => format!("#{idx}")
+                };
+                ((location.clone(), id), ext)
+            })
+        })
+}
+
+impl Wcif {
+    pub fn diff<'a>(&'a self, new: &'a Wcif) -> WcifDiff<'a> {
+        WcifDiff::new(self, new)
+    }
+}
+
+impl Display for PersonRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.registrant_id {
+            Some(id) => write!(f, "{} ({id})", self.name),
+            None => write!(f, "{} (user {})", self.name, self.wca_user_id)
+        }
+    }
+}
+
+impl Display for ActivityRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}] in {}, {} - {}", self.activity_code, self.id, self.room, self.start_time, self.end_time)
+    }
+}
+
+fn assignment_string(assignment: &Assignment) -> String {
+    let code = serde_json::to_string(&assignment.assignment_code).unwrap();
+    match assignment.station_number {
+        Some(station) => format!("{} for activity {} at station {station}", code.trim_matches('"'), assignment.activity_id),
+        None => format!("{} for activity {}", code.trim_matches('"'), assignment.activity_id)
+    }
+}
+
+fn json_or_none<T: Serialize>(value: Option<T>) -> String {
+    match value {
+        None => "none".to_string(),
+        Some(v) => serde_json::to_string(&v).unwrap()
+    }
+}
+
+impl Display for WcifDiff<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for person in &self.persons_added {
+            writeln!(f, "+ person {person}")?;
+        }
+        for person in &self.persons_removed {
+            writeln!(f, "- person {person}")?;
+        }
+        for change in &self.assignments_changed {
+            writeln!(f, "~ assignments for {}", change.person)?;
+            for assignment in &change.added {
+                writeln!(f, "    + {}", assignment_string(assignment))?;
+            }
+            for assignment in &change.removed {
+                writeln!(f, "    - {}", assignment_string(assignment))?;
+            }
+        }
+        for activity in &self.activities_added {
+            writeln!(f, "+ activity {activity}")?;
+        }
+        for activity in &self.activities_removed {
+            writeln!(f, "- activity {activity}")?;
+        }
+        for activity in &self.activities_moved {
+            writeln!(f, "~ activity {}", activity.old)?;
+            writeln!(f, "    -> {}", activity.new)?;
+        }
+        for change in &self.rounds_changed {
+            match &change.field {
+                RoundField::Added => writeln!(f, "+ round {}", change.round_id)?,
+                RoundField::Removed => writeln!(f, "- round {}", change.round_id)?,
+                RoundField::Format(c) => writeln!(f, "~ round {} format: {} -> {}", change.round_id, c.old, c.new)?,
+                RoundField::TimeLimit(c) => writeln!(f, "~ round {} time limit: {} -> {}", change.round_id, json_or_none(c.old), json_or_none(c.new))?,
+                RoundField::Cutoff(c) => writeln!(f, "~ round {} cutoff: {} -> {}", change.round_id, json_or_none(c.old), json_or_none(c.new))?,
+                RoundField::AdvancementCondition(c) => writeln!(f, "~ round {} advancement: {} -> {}", change.round_id, json_or_none(c.old), json_or_none(c.new))?,
+            }
+        }
+        for change in &self.extensions_changed {
+            let sign = match (change.old, change.new) {
+                (None, _) => '+',
+                (_, None) => '-',
+                _ => '~'
+            };
+            writeln!(f, "{sign} extension {} on {}: {} -> {}", change.id, change.location, json_or_none(change.old), json_or_none(change.new))?;
+        }
+        Ok(())
+    }
+}
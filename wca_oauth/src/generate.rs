@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+
+use serde_with::chrono::Duration;
+
+use crate::*;
+
+/// Builds plausible, reproducible wcifs for tests and benchmarks. The same settings and seed
+/// always give the same wcif. Competitors, rooms and days of 0 are generated as 1.
+#[derive(Debug, Clone)]
+pub struct WcifGenerator {
+    pub seed: u64,
+    pub competitors: usize,
+    pub events: Vec<String>,
+    /// Maximum number of rounds per event. Small and long events get fewer.
+    pub rounds: usize,
+    pub rooms: usize,
+    pub days: usize,
+    pub start_date: Date,
+}
+
+//(event id, median average or single, minutes per competitor per round, popularity). Medians are
+//in centiseconds, apart from fewest moves in moves times 100 and multi blind in a third of a point.
+pub(crate) const EVENT_INFO: [(&str, usize, usize, f64); 17] = [
+    ("333", 2200, 2, 0.95),
+    ("222", 700, 1, 0.75),
+    ("444", 7000, 4, 0.6),
+    ("555", 13000, 6, 0.45),
+    ("666", 26000, 8, 0.25),
+    ("777", 38000, 10, 0.2),
+    ("333oh", 3000, 2, 0.45),
+    ("333fm", 4500, 0, 0.15),
+    ("333bf", 15000, 3, 0.2),
+    ("444bf", 90000, 10, 0.05),
+    ("555bf", 180000, 15, 0.04),
+    ("pyram", 900, 1, 0.6),
+    ("minx", 10000, 5, 0.35),
+    ("sq1", 3000, 2, 0.35),
+    ("skewb", 800, 1, 0.55),
+    ("333mbf", 30, 0, 0.1),
+    ("clock", 1500, 1, 0.3),
+];
+
+const COLORS: [&str; 6] = ["#304a96", "#c23b22", "#3b8c2e", "#d1a319", "#7a3c9e", "#2e8c8c"];
+const FIRST_NAMES: [&str; 16] = ["Anna", "Ben", "Clara", "David", "Emma", "Felix", "Greta", "Hugo", "Ida", "Jonas", "Karin", "Lars", "Maja", "Niels", "Olivia", "Peter"];
+const LAST_NAMES: [&str; 16] = ["Andersen", "Berg", "Christensen", "Dahl", "Eriksen", "Frost", "Gram", "Holm", "Iversen", "Jensen", "Kjær", "Larsen", "Madsen", "Nielsen", "Olsen", "Poulsen"];
+const COUNTRIES: [&str; 5] = ["DK", "DK", "DK", "SE", "NO"];
+
+impl WcifGenerator {
+    pub fn new(seed: u64, competitors: usize) -> WcifGenerator {
+        WcifGenerator {
+            seed,
+            competitors,
+            events: ["333", "222", "444", "555", "333oh", "pyram", "skewb", "clock"].iter().map(|e| e.to_string()).collect(),
+            rounds: 3,
+            rooms: 1,
+            days: 2,
+            start_date: Date::from_ymd_opt(2022, 9, 3).unwrap(),
+        }
+    }
+
+    pub fn generate(&self) -> Wcif {
+        let generator = WcifGenerator {
+            competitors: self.competitors.max(1),
+            rooms: self.rooms.max(1),
+            days: self.days.max(1),
+            ..self.clone()
+        };
+        generator.generate_checked()
+    }
+
+    fn generate_checked(&self) -> Wcif {
+        let mut rng = Rng::new(self.seed);
+        let events: Vec<_> = self.events.iter()
+            .map(|id| EVENT_INFO.iter().find(|info| info.0 == id).unwrap_or_else(|| panic!("Unable to generate event {}", id)))
+            .collect();
+        let mut persons: Vec<_> = (1..=self.competitors).map(|id| self.person(&mut rng, id, &events)).collect();
+        //Make sure that every event has at least one competitor with a result, otherwise it would not be held.
+        for (event, ..) in &events {
+            if !persons.iter().any(|p| p.registration.as_ref().unwrap().event_ids.iter().any(|e| e == event)) {
+                let person = &mut persons[rng.below(self.competitors)];
+                person.registration.as_mut().unwrap().event_ids.push(event.to_string());
+            }
+        }
+        for (event, median, ..) in &events {
+            if !persons.iter().any(|p| p.personal_bests.iter().any(|pb| &pb.event_id == event)) {
+                let person = persons.iter_mut().find(|p| p.registration.as_ref().unwrap().event_ids.iter().any(|e| e == event)).unwrap();
+                person.personal_bests.extend(personal_bests(&mut rng, event, *median, 1.0));
+            }
+        }
+        rank_personal_bests(&mut persons);
+
+        let registered: Vec<_> = events.iter()
+            .map(|info| persons.iter().filter(|p| p.registration.as_ref().unwrap().event_ids.iter().any(|e| e == info.0)).count())
+            .collect();
+        let events: Vec<_> = events.iter().zip(&registered).map(|(info, registered)| self.event(info, *registered)).collect();
+        let schedule = self.schedule(&events, &registered);
+
+        Wcif {
+            format_version: "1.0".to_string(),
+            id: format!("Generated{}", self.seed),
+            name: format!("Generated Open {}", self.seed),
+            short_name: format!("Generated {}", self.seed),
+            persons,
+            events,
+            schedule,
+            competitor_limit: Some(self.competitors),
+            extensions: vec![],
+        }
+    }
+
+    fn person(&self, rng: &mut Rng, id: usize, events: &[&(&str, usize, usize, f64)]) -> Person {
+        //A smaller skill factor is a faster competitor. Newcomers have no wca id and no results.
+        let skill = (rng.normal() * 0.6).exp();
+        let newcomer = rng.chance(0.2);
+        let event_ids: Vec<_> = events.iter()
+            .filter(|(_, _, _, popularity)| rng.chance(*popularity))
+            .map(|(event, ..)| event.to_string())
+            .collect();
+        let mut pbs = vec![];
+        for (event, median, ..) in events.iter().filter(|_| !newcomer) {
+            //Some competitors have results in events they did not register for.
+            if event_ids.iter().any(|e| e == event) || rng.chance(0.1) {
+                pbs.extend(personal_bests(rng, event, *median, skill));
+            }
+        }
+        let roles = match id {
+            1 => vec![Role::Delegate],
+            2 | 3 => vec![Role::Organizer],
+            _ if rng.chance(0.05) => vec![Role::Other("staff-dataentry".to_string())],
+            _ => vec![]
+        };
+        let year = 2022 - 8 - rng.below(40) as i32;
+        let birthdate = Date::from_ymd_opt(year, 1 + rng.below(12) as u32, 1 + rng.below(28) as u32).unwrap();
+        let name = format!("{} {}", FIRST_NAMES[rng.below(FIRST_NAMES.len())], LAST_NAMES[rng.below(LAST_NAMES.len())]);
+        Person {
+            registrant_id: Some(id),
+            name: name.clone(),
+            wca_user_id: 1000 + id,
+            wca_id: match newcomer {
+                true => None,
+                false => Some(WcaId { year: 2008 + rng.below(14) as u16, chars: crate::anonymize::pseudonym_chars(id / 100), id: (id % 100) as u8 })
+            },
+            country_iso_2: COUNTRIES[rng.below(COUNTRIES.len())].to_string(),
+            gender: if rng.chance(0.5) { 'm' } else { 'f' },
+            birthdate,
+            email: format!("{}{id}@example.org", name.to_lowercase().replace(' ', ".")),
+            avatar: None,
+            roles,
+            registration: Some(Registration {
+                wca_registration_id: 5000 + id,
+                event_ids,
+                status: "accepted".to_string(),
+                guests: rng.below(3),
+                comments: String::new()
+            }),
+            assignments: vec![],
+            personal_bests: pbs
+        }
+    }
+
+    fn event(&self, (id, _, _, _): &(&str, usize, usize, f64), competitors: usize) -> Event {
+        let max_rounds = match *id {
+            "666" | "777" | "444bf" | "555bf" | "333mbf" => 1,
+            "333fm" | "333bf" | "minx" | "sq1" | "clock" => 2,
+            _ => 4
+        };
+        //Each round has 75% of the previous one and a round needs at least eight competitors to advance from.
+        let mut rounds = 1;
+        let mut remaining = competitors;
+        while rounds < self.rounds.min(max_rounds) && remaining * 3 / 4 >= 8 {
+            remaining = remaining * 3 / 4;
+            rounds += 1;
+        }
+        let (format, time_limit, cutoff) = match *id {
+            "666" | "777" => ('m', Some(time_limit(id)), Some(Cutoff { number_of_attempts: 1, attempt_result: AttemptResult::Ok(if *id == "666" { 30000 } else { 42000 }) })),
+            "333bf" | "444bf" | "555bf" => ('3', Some(time_limit(id)), None),
+            "333fm" => ('m', None, None),
+            "333mbf" => ('1', None, None),
+            "444" | "555" | "minx" | "sq1" | "clock" => ('a', Some(time_limit(id)), Some(Cutoff { number_of_attempts: 2, attempt_result: AttemptResult::Ok(EVENT_INFO.iter().find(|i| &i.0 == id).unwrap().1 * 2) })),
+            _ => ('a', Some(time_limit(id)), None)
+        };
+        Event {
+            id: id.to_string(),
+            rounds: (1..=rounds).map(|round| Round {
+                id: format!("{id}-r{round}"),
+                format,
+                time_limit: time_limit.clone(),
+                cutoff: cutoff.clone(),
+                advancement_condition: match round {
+                    _ if round == rounds => None,
+                    _ if round + 1 == rounds && rounds > 2 => Some(AdvancementCondition::Ranking(12)),
+                    _ => Some(AdvancementCondition::Percent(75))
+                },
+                results: vec![],
+                scramble_set_count: 1,
+                extensions: vec![],
+            }).collect(),
+            competitor_limit: None,
+            qualification: None,
+            extensions: vec![],
+        }
+    }
+
+    fn schedule(&self, events: &[Event], registered: &[usize]) -> Schedule {
+        //Rounds are held in order of round number, spread evenly over the days and the rooms of each day.
+        let mut rounds: Vec<_> = events.iter().zip(registered)
+            .flat_map(|(e, registered)| e.rounds.iter().enumerate().map(move |(idx, r)| (idx, e, r, (0..idx).fold(*registered, |acc, _| acc * 3 / 4))))
+            .collect();
+        //Within each round number the biggest rounds are placed first and last, so most competitors
+        //are around for the whole day, and the smaller rounds are placed in the middle.
+        rounds.sort_by_key(|(idx, _, _, competitors)| (*idx, std::cmp::Reverse(*competitors)));
+        let mut ordered = Vec::with_capacity(rounds.len());
+        for level in rounds.chunk_by(|a, b| a.0 == b.0) {
+            let front = level.iter().step_by(2);
+            let back = level.iter().skip(1).step_by(2).rev();
+            ordered.extend(front.chain(back).cloned());
+        }
+        let rounds = ordered;
+        let per_day = rounds.len().div_ceil(self.days);
+        let mut rooms: Vec<_> = (0..self.rooms).map(|idx| Room {
+            id: idx + 1,
+            name: format!("Room {}", idx + 1),
+            color: COLORS[idx % COLORS.len()].to_string(),
+            activities: vec![],
+            extensions: vec![],
+        }).collect();
+        let mut activity_id = 1;
+        let mut next_id = || {
+            activity_id += 1;
+            activity_id - 1
+        };
+        for (day, day_rounds) in rounds.chunks(per_day.max(1)).enumerate() {
+            let morning = (self.start_date + Duration::days(day as i64)).and_hms_opt(9, 0, 0).unwrap();
+            let mut clocks = vec![morning; self.rooms];
+            for (idx, (round_idx, event, round, competitors)) in day_rounds.iter().enumerate() {
+                let room = idx % self.rooms;
+                //Halfway through the day the first room gets a lunch break.
+                if room == 0 && idx == day_rounds.len() / 2 {
+                    let end = clocks[0] + Duration::minutes(60);
+                    rooms[0].activities.push(activity(next_id(), "Lunch".to_string(), "other-lunch".to_string(), clocks[0], end, vec![]));
+                    clocks[0] = end;
+                }
+                let minutes = EVENT_INFO.iter().find(|info| info.0 == event.id).unwrap().2;
+                let start = clocks[room];
+                let name = format!("{}, Round {}", event.id, round_idx + 1);
+                let act = match minutes {
+                    //Fewest moves and multi blind are held as one activity per attempt.
+                    0 => {
+                        let attempts = if event.id == "333mbf" { 1 } else { 3 };
+                        (1..=attempts).map(|a| {
+                            let start = start + Duration::minutes(75 * (a - 1));
+                            activity(next_id(), format!("{name}, Attempt {a}"), format!("{}-a{a}", round.id), start, start + Duration::minutes(60), vec![])
+                        }).collect()
+                    }
+                    _ => {
+                        let length = ((competitors * minutes / 10).div_ceil(15) * 15).clamp(30, 180) as i64;
+                        vec![activity(next_id(), name, round.id.clone(), start, start + Duration::minutes(length), vec![])]
+                    }
+                };
+                clocks[room] = act.last().unwrap().end_time + Duration::minutes(15);
+                rooms[room].activities.extend(act);
+            }
+        }
+        Schedule {
+            start_date: self.start_date,
+            number_of_days: self.days,
+            venues: vec![Venue {
+                id: 1,
+                name: "Generated Hall".to_string(),
+                latitude_microdegrees: 55676098,
+                longitude_microdegrees: 12568337,
+                country_iso_2: "DK".to_string(),
+                timezone: "Europe/Copenhagen".to_string(),
+                rooms,
+                extensions: vec![],
+            }],
+        }
+    }
+}
+
+fn activity(id: usize, name: String, activity_code: String, start_time: DateTime, end_time: DateTime, child_activities: Vec<Activity>) -> Activity {
+    Activity { id, name, activity_code, start_time, end_time, child_activities, scramble_set_id: None, extensions: vec![] }
+}
+
+fn time_limit(event: &str) -> TimeLimit {
+    let median = EVENT_INFO.iter().find(|info| info.0 == event).unwrap().1;
+    //Round the limit up to whole minutes
+    TimeLimit { centiseconds: (median * 4).div_ceil(6000) * 6000, cumulative_round_ids: vec![] }
+}
+
+fn personal_bests(rng: &mut Rng, event: &str, median: usize, skill: f64) -> Vec<PersonalBest> {
+    let noise = (rng.normal() * 0.1).exp();
+    let value = ((median as f64 * skill * noise) as usize).max(1);
+    let pb = |t: &str, best| PersonalBest { event_id: event.to_string(), best: AttemptResult::Ok(best), t: t.to_string(), world_ranking: 0, continental_ranking: 0, national_ranking: 0 };
+    match event {
+        //Multi blind results are encoded as 0DDTTTTTMM, see the wcif specification. Here an hour and none missed.
+        "333mbf" => {
+            let points = (value / 3).clamp(1, 40);
+            vec![pb("single", (99 - points) * 10000000 + 3600 * 100)]
+        }
+        //Fewest moves singles are in moves and averages in moves times 100.
+        "333fm" => {
+            let average = value.clamp(2000, 6000);
+            vec![pb("single", (average * 4 / 500).max(20)), pb("average", average)]
+        }
+        "333bf" | "444bf" | "555bf" => vec![pb("single", value)],
+        _ => vec![pb("single", value * 4 / 5), pb("average", value)]
+    }
+}
+
+fn rank_personal_bests(persons: &mut [Person]) {
+    let value = |best: &AttemptResult| if let AttemptResult::Ok(v) = best { *v } else { usize::MAX };
+    let mut all: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for pb in persons.iter().flat_map(|p| &p.personal_bests) {
+        all.entry((pb.event_id.clone(), pb.t.clone())).or_default().push(value(&pb.best));
+    }
+    for results in all.values_mut() {
+        results.sort_unstable();
+    }
+    for pb in persons.iter_mut().flat_map(|p| p.personal_bests.iter_mut()) {
+        let rank = 1 + all[&(pb.event_id.clone(), pb.t.clone())].partition_point(|v| *v < value(&pb.best));
+        pb.national_ranking = rank;
+        pb.continental_ranking = rank * 7;
+        pb.world_ranking = rank * 60;
+    }
+}
+
+/// Small splitmix64 generator, so generated wcifs do not depend on the version of an external crate.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn uniform(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.uniform() * n as f64) as usize
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        self.uniform() < p
+    }
+
+    fn normal(&mut self) -> f64 {
+        let u1 = self.uniform().max(f64::MIN_POSITIVE);
+        let u2 = self.uniform();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
@@ -2,6 +2,12 @@ mod wcif;
 mod oauth;
 mod competition;
 mod wcif_oauth;
+mod diff;
+mod anonymize;
+mod generate;
+mod results;
+mod countries;
+mod registration_csv;
 
 use serde::{Deserializer, Deserialize, Serializer};
 use serde::de::Error;
@@ -9,6 +15,12 @@ pub use wcif::*;
 pub use oauth::*;
 pub use wcif_oauth::*;
 pub use competition::*;
+pub use diff::*;
+pub use anonymize::*;
+pub use generate::*;
+pub use results::*;
+pub use registration_csv::*;
+pub use countries::{country_name, country_iso2};
 
 pub use serde_with::chrono::{NaiveDateTime as DateTime, NaiveDate as Date, NaiveTime as Time, Datelike};
 
@@ -35,7 +47,7 @@ where
 
 #[cfg(test)]
 mod test {
-    use crate::{parse, Wcif};
+    use crate::{parse, Wcif, WcifGenerator, Round, ResultsStore, Cutoff, TimeLimit, AttemptResult::*, AdvancementCondition, ResultError, WcifContainer, RegistrationCsvIssue, Assignment, AssignmentCode, RoundField, Change, Anonymizer, Date, Datelike, WcaId, EVENT_INFO};
 
     #[test]
     fn de() {
@@ -54,4 +66,268 @@ mod test {
             println!("{:?}, {:?}", a.activity_code, b.activity_code);
         }
     }
+
+    #[test]
+    fn anonymize() {
+        let original = serde_json::from_str::<Wcif>(include_str!("../wcif.json")).unwrap();
+        let mut wcif = serde_json::from_str::<Wcif>(include_str!("../wcif.json")).unwrap();
+        wcif.anonymize();
+        assert_eq!(original.persons.len(), wcif.persons.len());
+        for (a, b) in original.persons.iter().zip(wcif.persons.iter()) {
+            assert_ne!(a.name, b.name);
+            assert_ne!(a.email, b.email);
+            assert_eq!(a.registrant_id, b.registrant_id);
+            assert_eq!(a.registration.as_ref().map(|r| &r.event_ids), b.registration.as_ref().map(|r| &r.event_ids));
+            assert_eq!(a.assignments, b.assignments);
+            assert_eq!(a.personal_bests, b.personal_bests);
+            assert_eq!(b.birthdate, Date::from_ymd_opt(a.birthdate.year(), 1, 1).unwrap());
+            assert!(b.avatar.is_none());
+            assert_eq!(a.wca_id.is_some(), b.wca_id.is_some());
+            if let Some(new_id) = &b.wca_id {
+                let new_id = new_id.to_string();
+                assert_eq!(a.wca_id.as_ref().unwrap().year, b.wca_id.as_ref().unwrap().year);
+                assert_eq!(new_id.len(), 10);
+                assert!(new_id[..4].chars().all(|c| c.is_ascii_digit()), "{new_id}");
+                assert!(new_id[4..8].chars().all(|c| c.is_ascii_uppercase()), "{new_id}");
+                assert!(new_id[8..].chars().all(|c| c.is_ascii_digit()), "{new_id}");
+                assert_eq!(new_id.parse::<WcaId>().unwrap().to_string(), new_id);
+            }
+        }
+        assert_eq!(original.events, wcif.events);
+
+        //A later snapshot with persons in another order and one of them gone gets the same pseudonyms.
+        let mut anonymizer = Anonymizer::new();
+        let mut first = serde_json::from_str::<Wcif>(include_str!("../wcif.json")).unwrap();
+        let mut second = serde_json::from_str::<Wcif>(include_str!("../wcif.json")).unwrap();
+        second.persons.reverse();
+        second.persons.pop();
+        anonymizer.anonymize(&mut first);
+        anonymizer.anonymize(&mut second);
+        for person in &second.persons {
+            let same = first.persons.iter().find(|p| p.registrant_id == person.registrant_id).unwrap();
+            assert_eq!(same.name, person.name);
+            assert_eq!(same.wca_user_id, person.wca_user_id);
+            assert_eq!(same.wca_id, person.wca_id);
+        }
+    }
+
+    #[test]
+    fn generate() {
+        let generator = WcifGenerator::new(7, 150);
+        let wcif = generator.generate();
+        assert_eq!(wcif, generator.generate());
+        assert_eq!(wcif.persons.len(), 150);
+        let json = serde_json::to_string(&wcif).unwrap();
+        assert_eq!(wcif, serde_json::from_str::<Wcif>(&json).unwrap());
+
+        let mut empty = WcifGenerator::new(7, 0);
+        empty.rooms = 0;
+        empty.days = 0;
+        let wcif = empty.generate();
+        assert_eq!(wcif.persons.len(), 1);
+        assert_eq!(wcif.schedule.venues[0].rooms.len(), 1);
+    }
+
+    #[test]
+    fn generated_personal_bests() {
+        let mut generator = WcifGenerator::new(0, 40);
+        generator.events = EVENT_INFO.iter().map(|info| info.0.to_string()).collect();
+        generator.rounds = 1;
+        for seed in 0..100 {
+            generator.seed = seed;
+            let wcif = generator.generate();
+            for person in &wcif.persons {
+                let best = |event: &str, t: &str| person.personal_bests.iter()
+                    .find(|pb| pb.event_id == event && pb.t == t)
+                    .map(|pb| match pb.best { Ok(value) => value, _ => panic!("{event} {t} of {} is not a result", person.name) });
+                for (event, median, ..) in EVENT_INFO {
+                    let (single, average) = (best(event, "single"), best(event, "average"));
+                    match event {
+                        "333fm" => {
+                            assert!(single.is_none_or(|single| (20..=60).contains(&single)), "{event} single {single:?}");
+                            assert!(average.is_none_or(|average| (2000..=6000).contains(&average)), "{event} average {average:?}");
+                            assert_eq!(single.is_some(), average.is_some());
+                        }
+                        //0DDTTTTTMM with an hour and none missed.
+                        "333mbf" => {
+                            assert!(single.is_none_or(|single| (59..=98).contains(&(single / 10000000)) && single % 10000000 == 360000), "{event} single {single:?}");
+                            assert_eq!(average, None);
+                        }
+                        "333bf" | "444bf" | "555bf" => assert_eq!(average, None),
+                        _ => {
+                            assert!(average.is_none_or(|average| (median / 100..median * 100).contains(&average)), "{event} average {average:?}");
+                            assert_eq!(single.is_some(), average.is_some());
+                        }
+                    }
+                    if let (Some(mut single), Some(average)) = (single, average) {
+                        if event == "333fm" {
+                            single *= 100;
+                        }
+                        assert!(single <= average, "{event} single {single} above average {average}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn results() {
+        let round = Round {
+            id: "444-r1".to_string(),
+            format: 'a',
+            time_limit: Some(TimeLimit { centiseconds: 18000, cumulative_round_ids: vec![] }),
+            cutoff: Some(Cutoff { number_of_attempts: 2, attempt_result: Ok(9000) }),
+            advancement_condition: Some(AdvancementCondition::Ranking(2)),
+            results: vec![],
+            scramble_set_count: 1,
+            extensions: vec![]
+        };
+        let mut store = ResultsStore::new("Test");
+        store.enter(&round, 1, vec![Ok(6000), Ok(6500), DNF, Ok(5500), Ok(7000)]).unwrap();
+        store.enter(&round, 2, vec![Ok(8000), Ok(7000), Ok(7000), Ok(7500), Ok(9000)]).unwrap();
+        store.enter(&round, 3, vec![Ok(9500), DNF]).unwrap();
+        assert_eq!(store.enter(&round, 4, vec![Ok(9500), DNF, Ok(8000)]), Err(ResultError::CutoffNotMet { attempt: 3 }));
+        assert_eq!(store.enter(&round, 4, vec![Ok(19000)]), Err(ResultError::AboveTimeLimit { attempt: 1 }));
+        let results = store.results(&round);
+        assert_eq!(results.iter().map(|r| (r.person_id, r.ranking, r.average.clone())).collect::<Vec<_>>(),
+            vec![(1, Some(1), Ok(6500)), (2, Some(2), Ok(7500)), (3, Some(3), Skip)]);
+        assert_eq!(store.advancing(&round), vec![1, 2]);
+    }
+
+    #[test]
+    fn diff() {
+        let generator = WcifGenerator::new(5, 30);
+        let old = generator.generate();
+        let mut new = generator.generate();
+        assert!(old.diff(&new).is_empty());
+
+        let removed = new.persons.remove(0);
+        let mut added = WcifGenerator::new(6, 1).generate().persons.pop().unwrap();
+        added.wca_user_id = 99999;
+        new.persons.push(added);
+        let assignment = || Assignment { activity_id: 1, assignment_code: AssignmentCode::Judge, station_number: Some(3) };
+        new.persons[0].assignments.push(assignment());
+        let activity = &mut new.schedule.venues[0].rooms[0].activities[0];
+        activity.start_time += serde_with::chrono::Duration::minutes(30);
+        let moved = activity.id;
+        new.events[0].rounds[0].cutoff = Some(Cutoff { number_of_attempts: 2, attempt_result: Ok(3000) });
+        let round_id = new.events[0].rounds[0].id.clone();
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.persons_removed.iter().map(|p| p.wca_user_id).collect::<Vec<_>>(), vec![removed.wca_user_id]);
+        assert_eq!(diff.persons_added.iter().map(|p| p.wca_user_id).collect::<Vec<_>>(), vec![99999]);
+        assert_eq!(diff.assignments_changed.len(), 1);
+        assert_eq!(diff.assignments_changed[0].person.wca_user_id, new.persons[0].wca_user_id);
+        assert_eq!(diff.assignments_changed[0].added, vec![&assignment()]);
+        assert!(diff.assignments_changed[0].removed.is_empty());
+        assert_eq!(diff.activities_moved.len(), 1);
+        assert_eq!(diff.activities_moved[0].new.id, moved);
+        assert!(diff.activities_added.is_empty() && diff.activities_removed.is_empty());
+        assert_eq!(diff.rounds_changed.len(), 1);
+        assert_eq!(diff.rounds_changed[0].round_id, round_id);
+        assert!(matches!(diff.rounds_changed[0].field, RoundField::Cutoff(Change { old: None, new: Some(_) })));
+
+        let json: serde_json::Value = serde_json::from_str(&diff.to_json()).unwrap();
+        assert_eq!(json["personsAdded"][0]["wcaUserId"], 99999);
+        assert_eq!(json["personsRemoved"][0]["name"], removed.name.as_str());
+        assert_eq!(json["assignmentsChanged"][0]["added"][0]["stationNumber"], 3);
+        assert_eq!(json["activitiesMoved"][0]["old"]["id"], moved);
+        assert_eq!(json["roundsChanged"][0]["field"]["field"], "cutoff");
+        assert_eq!(json["roundsChanged"][0]["field"]["change"]["old"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn advancing() {
+        let round = |format, cutoff, advancement_condition| Round {
+            id: "444-r1".to_string(),
+            format,
+            time_limit: None,
+            cutoff,
+            advancement_condition: Some(advancement_condition),
+            results: vec![],
+            scramble_set_count: 1,
+            extensions: vec![]
+        };
+        let enter_all = |round: &Round, results: &[usize]| {
+            let mut store = ResultsStore::new("Test");
+            for (idx, result) in results.iter().enumerate() {
+                store.enter(round, idx + 1, vec![Ok(*result)]).unwrap();
+            }
+            store
+        };
+
+        //A tie at the cut advances together while it stays within 75%.
+        let ranking = round('1', None, AdvancementCondition::Ranking(3));
+        let store = enter_all(&ranking, &[1000, 1100, 1200, 1200, 1300, 1400, 1500, 1600]);
+        assert_eq!(store.advancing(&ranking), vec![1, 2, 3, 4]);
+        let percent = round('1', None, AdvancementCondition::Percent(25));
+        let store = enter_all(&percent, &[1000, 1100, 1100, 1300, 1400, 1500, 1600, 1700]);
+        assert_eq!(store.advancing(&percent), vec![1, 2, 3]);
+
+        //A tie at the 75% cap would take too many through, so the tie does not advance.
+        let store = enter_all(&ranking, &[1000, 1100, 1200, 1200]);
+        assert_eq!(store.advancing(&ranking), vec![1, 2]);
+
+        //Only results better than the attempt result advance, and at most 75%.
+        let attempt_result = round('1', None, AdvancementCondition::AttemptResult(1200));
+        let store = enter_all(&attempt_result, &[1000, 1100, 1200, 1300, 1400, 1500]);
+        assert_eq!(store.advancing(&attempt_result), vec![1, 2]);
+        let store = enter_all(&attempt_result, &[1000, 1050, 1100, 1150]);
+        assert_eq!(store.advancing(&attempt_result), vec![1, 2, 3]);
+
+        //A DNF average with a valid single is ranked by the single ahead of a missed cutoff, and
+        //a competitor with only DNFs never advances.
+        let cutoff = Some(Cutoff { number_of_attempts: 2, attempt_result: Ok(9000) });
+        let average = round('a', cutoff, AdvancementCondition::Ranking(3));
+        let mut store = ResultsStore::new("Test");
+        store.enter(&average, 1, vec![Ok(6000), Ok(6500), Ok(6000), Ok(7000), Ok(6500)]).unwrap();
+        store.enter(&average, 2, vec![Ok(8000), DNF, DNF, Ok(7000), Ok(7500)]).unwrap();
+        store.enter(&average, 3, vec![Ok(9500), Ok(9800)]).unwrap();
+        store.enter(&average, 4, vec![DNF, DNF]).unwrap();
+        let results = store.results(&average);
+        assert_eq!(results.iter().map(|r| (r.person_id, r.ranking, r.average.clone())).collect::<Vec<_>>(),
+            vec![(1, Some(1), Ok(6333)), (2, Some(2), DNF), (3, Some(3), Skip), (4, Some(4), Skip)]);
+        assert_eq!(store.advancing(&average), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn registration_csv() {
+        let mut cont = WcifContainer::new(WcifGenerator::new(3, 40).generate());
+        let csv = cont.registration_csv();
+        let import = cont.import_registration_csv(&csv);
+        assert!(import.issues.is_empty());
+        assert!(import.added.is_empty());
+        assert_eq!(import.updated.len(), 40);
+
+        let header = csv.lines().next().unwrap();
+        let events = header.split(',').count() - 9;
+        let zeros = vec!["0"; events - 1].join(",");
+        let first = cont.get().persons[0].name.clone();
+        let rows = [
+            format!("a,Walk In,\"Hong Kong, China\",,2010-05-01,f,1,{zeros},,0,"),
+            format!("a,{first},Japan,,2000-01-01,m,1,{zeros},,0,"),
+            format!("a,No Birthdate,Denmark,,,m,1,{zeros},,0,"),
+            "a,Too short".to_string(),
+            format!("a,Second Walk In,Denmark,,2011-03-01,m,0,{zeros},,0,"),
+        ];
+        let import = cont.import_registration_csv(&format!("{header}\n{}", rows.join("\n")));
+        assert_eq!(import.added, vec![41, 42]);
+        let walk_in = cont.persons_iter().find(|p| p.registrant_id == Some(41)).unwrap();
+        assert_eq!(walk_in.country_iso_2, "HK");
+        assert_eq!(walk_in.registration.as_ref().unwrap().event_ids.len(), 1);
+        //Walk-ins are told apart from each other and from everyone else by their user id.
+        let mut user_ids: Vec<_> = cont.persons_iter().map(|p| p.wca_user_id).collect();
+        user_ids.sort();
+        user_ids.dedup();
+        assert_eq!(user_ids.len(), 42);
+        assert!(import.issues.iter().any(|i| matches!(i, RegistrationCsvIssue::Mismatch { line: 3, column, .. } if column == "Country")));
+        assert!(import.issues.iter().any(|i| matches!(i, RegistrationCsvIssue::MissingField { line: 4, .. })));
+        assert!(import.issues.iter().any(|i| matches!(i, RegistrationCsvIssue::MalformedLine { line: 5, .. })));
+
+        //Importing without event columns keeps the events.
+        let events = cont.get().persons[0].registration.as_ref().unwrap().event_ids.clone();
+        let import = cont.import_registration_csv(&format!("Name\n{first}"));
+        assert_eq!(import.updated.len(), 1);
+        assert_eq!(cont.get().persons[0].registration.as_ref().unwrap().event_ids, events);
+    }
 }
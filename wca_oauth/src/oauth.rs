@@ -276,12 +276,12 @@ impl WcifContainer {
     }
 }
 
-struct ActivityIter<'a> {
+pub(crate) struct ActivityIter<'a> {
     activites: Vec<Box<dyn Iterator<Item = &'a Activity> + 'a>>
 }
 
 impl<'a> ActivityIter<'a> {
-    fn new(vec: &'a Vec<Activity>) -> Self {
+    pub(crate) fn new(vec: &'a Vec<Activity>) -> Self {
         ActivityIter {
             activites: vec![Box::new(vec.iter())]
         }
@@ -0,0 +1,334 @@
+use std::fmt::{Display, Formatter};
+
+use crate::*;
+use crate::countries::{country_iso2, country_name};
+
+/// Summary of a registration csv import. Registrant ids refer to the persons in the wcif.
+#[derive(Debug, Default, PartialEq)]
+pub struct RegistrationImport {
+    pub added: Vec<usize>,
+    pub updated: Vec<usize>,
+    pub issues: Vec<RegistrationCsvIssue>,
+}
+
+/// Something in the csv which could not be used as is. Lines are counted from 1, header included.
+#[derive(Debug, PartialEq)]
+pub enum RegistrationCsvIssue {
+    MissingColumn { column: String },
+    MalformedLine { line: usize, reason: String },
+    InvalidField { line: usize, column: String, value: String },
+    /// The csv and the wcif disagree about a person. The value in the wcif is kept.
+    Mismatch { line: usize, name: String, column: String, wcif: String, csv: String },
+    /// A walk-in could not be added, as the wcif needs the value for every person.
+    MissingField { line: usize, name: String, column: String },
+}
+
+impl Display for RegistrationCsvIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistrationCsvIssue::MissingColumn { column } => write!(f, "The csv has no \"{column}\" column"),
+            RegistrationCsvIssue::MalformedLine { line, reason } => write!(f, "Line {line}: {reason}"),
+            RegistrationCsvIssue::InvalidField { line, column, value } => write!(f, "Line {line}: \"{value}\" is not a valid value for {column}"),
+            RegistrationCsvIssue::Mismatch { line, name, column, wcif, csv } => write!(f, "Line {line}: {column} of {name} is \"{wcif}\" in the wcif, but \"{csv}\" in the csv"),
+            RegistrationCsvIssue::MissingField { line, name, column } => write!(f, "Line {line}: {name} is not in the wcif and can not be added without {column}"),
+        }
+    }
+}
+
+const NAME: &str = "Name";
+const STATUS: &str = "Status";
+const COUNTRY: &str = "Country";
+const WCA_ID: &str = "WCA ID";
+const BIRTH_DATE: &str = "Birth Date";
+const GENDER: &str = "Gender";
+const EMAIL: &str = "Email";
+const GUESTS: &str = "Guests";
+const IP: &str = "IP";
+
+/// A row of the csv with every field parsed. Fields which are missing or invalid are `None`.
+struct Row {
+    line: usize,
+    name: String,
+    status: Option<String>,
+    country: Option<String>,
+    wca_id: Option<WcaId>,
+    birthdate: Option<Date>,
+    gender: Option<char>,
+    email: Option<String>,
+    guests: Option<usize>,
+    /// None when the csv has no event columns.
+    event_ids: Option<Vec<String>>,
+}
+
+impl WcifContainer {
+    /// Registrations in the layout of the csv export on the WCA website.
+    pub fn registration_csv(&self) -> String {
+        let event_ids: Vec<_> = self.events_iter().map(|e| e.id.as_str()).collect();
+        let mut header = vec![STATUS, NAME, COUNTRY, WCA_ID, BIRTH_DATE, GENDER];
+        header.extend(event_ids.iter());
+        header.extend([EMAIL, GUESTS, IP]);
+        let mut csv = csv_line(header.into_iter().map(str::to_string));
+        for person in self.persons_iter() {
+            let registration = match &person.registration {
+                None => continue,
+                Some(v) => v
+            };
+            let status = match registration.status.as_str() {
+                "accepted" => "a",
+                "pending" => "p",
+                "deleted" => "d",
+                s => s
+            };
+            let mut fields = vec![
+                status.to_string(),
+                person.name.clone(),
+                country_name(&person.country_iso_2).unwrap_or(&person.country_iso_2).to_string(),
+                person.wca_id.as_ref().map(|id| id.to_string()).unwrap_or_default(),
+                person.birthdate.format("%Y-%m-%d").to_string(),
+                person.gender.to_string(),
+            ];
+            fields.extend(event_ids.iter().map(|id| match registration.event_ids.iter().any(|e| e == id) {
+                true => "1".to_string(),
+                false => "0".to_string()
+            }));
+            fields.extend([person.email.clone(), registration.guests.to_string(), String::new()]);
+            csv.push_str(&csv_line(fields.into_iter()));
+        }
+        csv
+    }
+
+    /// Merges a registration csv into the wcif. Rows are matched to persons by WCA ID and otherwise
+    /// by name. Matched persons get their events, status and guests updated, while rows matching
+    /// nobody are added as walk-ins. Events are only updated when the csv has event columns.
+    /// Personal data is never overwritten, differences are reported.
+    pub fn import_registration_csv(&mut self, csv: &str) -> RegistrationImport {
+        let mut import = RegistrationImport::default();
+        let mut records = parse_csv(csv.trim_start_matches('\u{feff}')).into_iter();
+        let header = match records.next() {
+            None => {
+                import.issues.push(RegistrationCsvIssue::MissingColumn { column: NAME.to_string() });
+                return import;
+            }
+            Some((_, Err(reason))) => {
+                import.issues.push(RegistrationCsvIssue::MalformedLine { line: 1, reason });
+                return import;
+            }
+            Some((_, Ok(v))) => v
+        };
+        let column = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+        let name_column = match column(NAME) {
+            None => {
+                import.issues.push(RegistrationCsvIssue::MissingColumn { column: NAME.to_string() });
+                return import;
+            }
+            Some(v) => v
+        };
+        let events: Vec<_> = self.events_iter()
+            .filter_map(|e| column(&e.id).map(|idx| (e.id.clone(), idx)))
+            .collect();
+
+        for (line, record) in records {
+            let fields = match record {
+                Err(reason) => {
+                    import.issues.push(RegistrationCsvIssue::MalformedLine { line, reason });
+                    continue;
+                }
+                Ok(v) if v.iter().all(|f| f.trim().is_empty()) => continue,
+                Ok(v) if v.len() != header.len() => {
+                    let reason = format!("Expected {} fields, but found {}", header.len(), v.len());
+                    import.issues.push(RegistrationCsvIssue::MalformedLine { line, reason });
+                    continue;
+                }
+                Ok(v) => v
+            };
+            let name = fields[name_column].trim().to_string();
+            if name.is_empty() {
+                import.issues.push(RegistrationCsvIssue::MalformedLine { line, reason: "The name is empty".to_string() });
+                continue;
+            }
+            let issues = &mut import.issues;
+            let status = parse_field(&fields, column(STATUS), STATUS, line, issues, |v| match v.to_lowercase().as_str() {
+                "a" | "accepted" => Some("accepted".to_string()),
+                "p" | "pending" => Some("pending".to_string()),
+                "d" | "deleted" => Some("deleted".to_string()),
+                _ => None
+            });
+            let country = parse_field(&fields, column(COUNTRY), COUNTRY, line, issues, |v| country_iso2(v).map(str::to_string));
+            let wca_id = parse_field(&fields, column(WCA_ID), WCA_ID, line, issues, |v| v.to_uppercase().parse::<WcaId>().ok());
+            let birthdate = parse_field(&fields, column(BIRTH_DATE), BIRTH_DATE, line, issues, |v| Date::parse_from_str(v, "%Y-%m-%d").ok());
+            let gender = parse_field(&fields, column(GENDER), GENDER, line, issues, |v| match v.to_lowercase().as_str() {
+                "m" | "male" => Some('m'),
+                "f" | "female" => Some('f'),
+                "o" | "other" => Some('o'),
+                _ => None
+            });
+            let email = parse_field(&fields, column(EMAIL), EMAIL, line, issues, |v| Some(v.to_string()));
+            let guests = parse_field(&fields, column(GUESTS), GUESTS, line, issues, |v| v.parse::<usize>().ok());
+            let event_ids: Vec<_> = events.iter()
+                .filter(|(event_id, idx)| match fields[*idx].trim() {
+                    "1" => true,
+                    "0" | "" => false,
+                    value => {
+                        issues.push(RegistrationCsvIssue::InvalidField { line, column: event_id.clone(), value: value.to_string() });
+                        false
+                    }
+                })
+                .map(|(event_id, _)| event_id.clone())
+                .collect();
+            //Without event columns the events are not part of the import.
+            let event_ids = Some(event_ids).filter(|_| !events.is_empty());
+            let row = Row { line, name, status, country, wca_id, birthdate, gender, email, guests, event_ids };
+            self.merge_row(row, &mut import);
+        }
+        import
+    }
+
+    fn merge_row(&mut self, row: Row, import: &mut RegistrationImport) {
+        let by_id = row.wca_id.as_ref().and_then(|id| self.wcif.persons.iter().position(|p| p.wca_id.as_ref() == Some(id)));
+        let existing = by_id.or_else(|| self.wcif.persons.iter().position(|p| p.name.trim().eq_ignore_ascii_case(&row.name)));
+        match existing {
+            Some(idx) => {
+                let person = &mut self.wcif.persons[idx];
+                let mut mismatch = |column: &str, wcif: String, csv: String| {
+                    if wcif != csv {
+                        import.issues.push(RegistrationCsvIssue::Mismatch { line: row.line, name: person.name.clone(), column: column.to_string(), wcif, csv });
+                    }
+                };
+                mismatch(NAME, person.name.clone(), row.name.clone());
+                if let Some(wca_id) = &row.wca_id {
+                    mismatch(WCA_ID, person.wca_id.as_ref().map(|id| id.to_string()).unwrap_or_default(), wca_id.to_string());
+                }
+                if let Some(country) = &row.country {
+                    mismatch(COUNTRY, person.country_iso_2.clone(), country.clone());
+                }
+                if let Some(birthdate) = row.birthdate {
+                    mismatch(BIRTH_DATE, person.birthdate.to_string(), birthdate.to_string());
+                }
+                if let Some(gender) = row.gender {
+                    mismatch(GENDER, person.gender.to_string(), gender.to_string());
+                }
+                let registration = person.registration.get_or_insert_with(|| Registration {
+                    wca_registration_id: 0,
+                    event_ids: vec![],
+                    status: "accepted".to_string(),
+                    guests: 0,
+                    comments: String::new()
+                });
+                if let Some(event_ids) = row.event_ids {
+                    registration.event_ids = event_ids;
+                }
+                if let Some(status) = row.status {
+                    registration.status = status;
+                }
+                if let Some(guests) = row.guests {
+                    registration.guests = guests;
+                }
+                if let Some(registrant_id) = person.registrant_id {
+                    import.updated.push(registrant_id);
+                }
+            }
+            None => {
+                let missing = [(COUNTRY, row.country.is_none()), (BIRTH_DATE, row.birthdate.is_none()), (GENDER, row.gender.is_none())];
+                if let Some((column, _)) = missing.iter().find(|(_, missing)| *missing) {
+                    import.issues.push(RegistrationCsvIssue::MissingField { line: row.line, name: row.name, column: column.to_string() });
+                    return;
+                }
+                let registrant_id = self.persons_iter().filter_map(|p| p.registrant_id).max().unwrap_or(0) + 1;
+                //Walk-ins have no WCA account, but persons are told apart by their user id, so every
+                //walk-in gets one above those in use.
+                let wca_user_id = self.persons_iter().map(|p| p.wca_user_id).max().unwrap_or(0) + 1;
+                self.wcif.persons.push(Person {
+                    registrant_id: Some(registrant_id),
+                    name: row.name,
+                    wca_user_id,
+                    wca_id: row.wca_id,
+                    country_iso_2: row.country.unwrap(),
+                    gender: row.gender.unwrap(),
+                    birthdate: row.birthdate.unwrap(),
+                    email: row.email.unwrap_or_default(),
+                    avatar: None,
+                    roles: vec![],
+                    registration: Some(Registration {
+                        wca_registration_id: 0,
+                        event_ids: row.event_ids.unwrap_or_default(),
+                        status: row.status.unwrap_or_else(|| "accepted".to_string()),
+                        guests: row.guests.unwrap_or(0),
+                        comments: String::new()
+                    }),
+                    assignments: vec![],
+                    personal_bests: vec![],
+                });
+                import.added.push(registrant_id);
+            }
+        }
+    }
+}
+
+/// Parses a field if the column exists and the field is not empty. Invalid values are reported.
+fn parse_field<T>(fields: &[String], column: Option<usize>, name: &str, line: usize, issues: &mut Vec<RegistrationCsvIssue>, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    let value = fields[column?].trim();
+    if value.is_empty() {
+        return None;
+    }
+    let parsed = parse(value);
+    if parsed.is_none() {
+        issues.push(RegistrationCsvIssue::InvalidField { line, column: name.to_string(), value: value.to_string() });
+    }
+    parsed
+}
+
+fn csv_line(fields: impl Iterator<Item = String>) -> String {
+    let mut line = fields
+        .map(|f| match f.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", f.replace('"', "\"\"")),
+            false => f
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push('\n');
+    line
+}
+
+/// Splits the csv into records, each with the line it starts on. Quoted fields may contain commas,
+/// escaped quotes and line breaks.
+fn parse_csv(csv: &str) -> Vec<(usize, std::result::Result<Vec<String>, String>)> {
+    let mut records = vec![];
+    let mut chars = csv.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut error = None;
+        while let Some(c) = chars.next() {
+            match (c, quoted) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                ('"', true) => quoted = false,
+                ('"', false) if field.is_empty() => quoted = true,
+                ('"', false) => error = Some("Unexpected quote in unquoted field".to_string()),
+                (',', false) => fields.push(std::mem::take(&mut field)),
+                ('\r', false) => (),
+                ('\n', false) => {
+                    line += 1;
+                    break;
+                }
+                (c, _) => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c)
+                }
+            }
+        }
+        if quoted {
+            error = Some("Quoted field is never closed".to_string());
+        }
+        fields.push(field);
+        records.push((start, error.map_or(Ok(fields), Err)));
+    }
+    records
+}
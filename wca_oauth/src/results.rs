@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Local store of results entered at the venue. Results are validated when entered, kept in a json
+/// file so nothing is lost without internet, and exported into the wcif when it is time to patch.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultsStore {
+    pub competition_id: String,
+    /// Entered attempts keyed by round id (e.g. `333-r1`) and then registrant id.
+    pub rounds: HashMap<String, HashMap<usize, Vec<AttemptResult>>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ResultError {
+    TooManyAttempts { expected: usize, received: usize },
+    CutoffNotMet { attempt: usize },
+    AboveTimeLimit { attempt: usize },
+    AboveCumulativeLimit { attempt: usize },
+}
+
+impl Display for ResultError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResultError::TooManyAttempts { expected, received } => write!(f, "Expected at most {expected} attempts, but received {received}"),
+            ResultError::CutoffNotMet { attempt } => write!(f, "Attempt {attempt} is given, but the cutoff was not met"),
+            ResultError::AboveTimeLimit { attempt } => write!(f, "Attempt {attempt} is above the time limit and should be a DNF"),
+            ResultError::AboveCumulativeLimit { attempt } => write!(f, "Attempt {attempt} exceeds the cumulative time limit and should be a DNF"),
+        }
+    }
+}
+
+impl std::error::Error for ResultError {}
+
+impl ResultsStore {
+    pub fn new(competition_id: &str) -> ResultsStore {
+        ResultsStore { competition_id: competition_id.to_string(), rounds: HashMap::new() }
+    }
+
+    /// A store with the attempts of every round which has results in the wcif, e.g. from WCA Live.
+    /// They are taken as they are, without validating them again.
+    pub fn import(wcif: &Wcif) -> ResultsStore {
+        let rounds = wcif.events.iter()
+            .flat_map(|e| e.rounds.iter())
+            .filter(|round| !round.results.is_empty())
+            .map(|round| {
+                let attempts = round.results.iter()
+                    .map(|r| (r.person_id, r.attempts.iter().map(|a| a.result.clone()).collect()))
+                    .collect();
+                (round.id.clone(), attempts)
+            })
+            .collect();
+        ResultsStore { competition_id: wcif.id.clone(), rounds }
+    }
+
+    /// Loads the store from disk. A missing file gives an empty store.
+    pub fn load(path: impl AsRef<Path>, competition_id: &str) -> std::io::Result<ResultsStore> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ResultsStore::new(competition_id)),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Writes to a temporary file first, so a crash while saving does not destroy the store.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Validates and stores the attempts of a person. Attempts not done yet can be left out.
+    pub fn enter(&mut self, round: &Round, person_id: usize, attempts: Vec<AttemptResult>) -> std::result::Result<(), ResultError> {
+        self.validate(round, person_id, &attempts)?;
+        self.rounds.entry(round.id.clone()).or_default().insert(person_id, attempts);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, round_id: &str, person_id: usize) -> Option<Vec<AttemptResult>> {
+        self.rounds.get_mut(round_id)?.remove(&person_id)
+    }
+
+    pub fn attempts(&self, round_id: &str, person_id: usize) -> Option<&Vec<AttemptResult>> {
+        self.rounds.get(round_id)?.get(&person_id)
+    }
+
+    fn validate(&self, round: &Round, person_id: usize, attempts: &[AttemptResult]) -> std::result::Result<(), ResultError> {
+        let expected = number_of_attempts(round.format);
+        if attempts.len() > expected {
+            return Err(ResultError::TooManyAttempts { expected, received: attempts.len() });
+        }
+        let event = event_id(&round.id);
+        if let Some(cutoff) = &round.cutoff {
+            let made = attempts.iter().take(cutoff.number_of_attempts).any(|a| better(a, &cutoff.attempt_result));
+            if attempts.len() > cutoff.number_of_attempts && !made {
+                if let Some(idx) = attempts.iter().skip(cutoff.number_of_attempts).position(|a| a != &AttemptResult::Skip) {
+                    return Err(ResultError::CutoffNotMet { attempt: cutoff.number_of_attempts + idx + 1 });
+                }
+            }
+        }
+        //Multi blind and fewest moves results are not times, so the limits do not apply in the same way.
+        let time_limit = match &round.time_limit {
+            Some(t) if event != "333mbf" && event != "333fm" => t,
+            _ => return Ok(())
+        };
+        if time_limit.cumulative_round_ids.is_empty() {
+            if let Some(idx) = attempts.iter().position(|a| matches!(a, AttemptResult::Ok(v) if *v >= time_limit.centiseconds)) {
+                return Err(ResultError::AboveTimeLimit { attempt: idx + 1 });
+            }
+            return Ok(());
+        }
+        //Cumulative limits include the attempts of the person in the other rounds sharing the limit.
+        let mut total: usize = time_limit.cumulative_round_ids.iter()
+            .filter(|id| *id != &round.id)
+            .filter_map(|id| self.attempts(id, person_id))
+            .flatten()
+            .map(time_spent)
+            .sum();
+        for (idx, attempt) in attempts.iter().enumerate() {
+            total += time_spent(attempt);
+            if let AttemptResult::Ok(_) = attempt {
+                if total >= time_limit.centiseconds {
+                    return Err(ResultError::AboveCumulativeLimit { attempt: idx + 1 });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes best, average and ranking for every person in the round, sorted by ranking.
+    pub fn results(&self, round: &Round) -> Vec<Result> {
+        let entered = match self.rounds.get(&round.id) {
+            None => return vec![],
+            Some(v) => v
+        };
+        let expected = number_of_attempts(round.format);
+        let event = event_id(&round.id);
+        let mut results: Vec<_> = entered.iter()
+            .map(|(person_id, attempts)| {
+                let mut attempts = attempts.clone();
+                attempts.resize(expected, AttemptResult::Skip);
+                let cutoff_met = round.cutoff.as_ref()
+                    .map(|c| attempts.iter().take(c.number_of_attempts).any(|a| better(a, &c.attempt_result)))
+                    .unwrap_or(true);
+                let best = best(&attempts);
+                let average = if cutoff_met { average(&attempts, round.format, event) } else { AttemptResult::Skip };
+                Result {
+                    person_id: *person_id,
+                    ranking: None,
+                    attempts: attempts.into_iter().map(|result| Attempt { result, reconstruction: None }).collect(),
+                    best,
+                    average
+                }
+            })
+            .collect();
+        let uses_average = matches!(round.format, 'a' | 'm');
+        let key = |r: &Result| match uses_average {
+            true => (sort_value(&r.average), sort_value(&r.best)),
+            false => (sort_value(&r.best), 0)
+        };
+        results.sort_by_key(|r| (key(r), r.person_id));
+        for idx in 0..results.len() {
+            let ranking = if idx > 0 && key(&results[idx - 1]) == key(&results[idx]) {
+                results[idx - 1].ranking
+            }
+            else {
+                Some(idx + 1)
+            };
+            results[idx].ranking = ranking;
+        }
+        results
+    }
+
+    /// Registrant ids of the competitors advancing to the next round according to the advancement
+    /// condition. Competitors tied at the cut all advance, unless that would be more than 75% of
+    /// the round. Competitors without a valid result never advance.
+    pub fn advancing(&self, round: &Round) -> Vec<usize> {
+        let results = self.results(round);
+        let condition = match &round.advancement_condition {
+            None => return vec![],
+            Some(v) => v
+        };
+        let uses_average = matches!(round.format, 'a' | 'm');
+        let result_of = |r: &Result| if uses_average { r.average.clone() } else { r.best.clone() };
+        let max = results.len() * 3 / 4;
+        let amount = match condition {
+            AdvancementCondition::Ranking(level) => *level,
+            AdvancementCondition::Percent(level) => results.len() * level / 100,
+            AdvancementCondition::AttemptResult(level) => results.iter()
+                .filter(|r| matches!(result_of(r), AttemptResult::Ok(v) if v < *level))
+                .count()
+        }.min(max);
+        let qualified: Vec<_> = results.iter()
+            .filter(|r| matches!(result_of(r), AttemptResult::Ok(_)) || (uses_average && matches!(r.best, AttemptResult::Ok(_))))
+            .filter(|r| r.ranking.unwrap() <= amount)
+            .collect();
+        //If a tie at the cut would take more than 75% through, nobody in the tie advances.
+        match qualified.len() > max {
+            true => {
+                let tied = qualified.last().unwrap().ranking;
+                qualified.iter().filter(|r| r.ranking != tied).map(|r| r.person_id).collect()
+            }
+            false => qualified.iter().map(|r| r.person_id).collect()
+        }
+    }
+
+    /// Writes the results of every round in the store into the `results` arrays of the wcif.
+    pub fn export(&self, wcif: &mut Wcif) {
+        for round in wcif.events.iter_mut().flat_map(|e| e.rounds.iter_mut()) {
+            if self.rounds.contains_key(&round.id) {
+                round.results = self.results(round);
+            }
+        }
+    }
+}
+
+impl WcifContainer {
+    pub fn apply_results(&mut self, store: &ResultsStore) {
+        store.export(&mut self.wcif)
+    }
+}
+
+pub fn number_of_attempts(format: char) -> usize {
+    match format {
+        '1' => 1,
+        '2' => 2,
+        '3' | 'm' => 3,
+        _ => 5
+    }
+}
+
+fn event_id(round_id: &str) -> &str {
+    round_id.split("-r").next().unwrap()
+}
+
+fn sort_value(result: &AttemptResult) -> usize {
+    match result {
+        AttemptResult::Ok(v) => *v,
+        AttemptResult::DNF => usize::MAX - 2,
+        AttemptResult::DNS => usize::MAX - 1,
+        AttemptResult::Skip => usize::MAX,
+    }
+}
+
+fn better(a: &AttemptResult, b: &AttemptResult) -> bool {
+    match (a, b) {
+        (AttemptResult::Ok(a), AttemptResult::Ok(b)) => a < b,
+        _ => false
+    }
+}
+
+fn time_spent(attempt: &AttemptResult) -> usize {
+    match attempt {
+        AttemptResult::Ok(v) => *v,
+        _ => 0
+    }
+}
+
+fn best(attempts: &[AttemptResult]) -> AttemptResult {
+    attempts.iter()
+        .filter(|a| a != &&AttemptResult::Skip)
+        .min_by_key(|a| sort_value(a))
+        .cloned()
+        .unwrap_or(AttemptResult::Skip)
+}
+
+fn average(attempts: &[AttemptResult], format: char, event: &str) -> AttemptResult {
+    let done: Vec<_> = attempts.iter().filter(|a| a != &&AttemptResult::Skip).collect();
+    let counting: Vec<_> = match format {
+        'a' if done.len() == 5 => {
+            let mut sorted = done.clone();
+            sorted.sort_by_key(|a| sort_value(a));
+            sorted[1..4].to_vec()
+        }
+        'm' | '3' if done.len() == 3 && event != "333mbf" => done,
+        _ => return AttemptResult::Skip
+    };
+    let mut sum = 0;
+    for attempt in counting {
+        match attempt {
+            AttemptResult::Ok(v) => sum += v,
+            _ => return AttemptResult::DNF
+        }
+    }
+    //Fewest moves means are stored multiplied by 100. Times above 10 minutes are rounded to seconds.
+    let mean = if event == "333fm" { (sum * 100 + 1) / 3 } else { (sum + 1) / 3 };
+    match mean {
+        v if event != "333fm" && v > 60000 => AttemptResult::Ok((v + 50) / 100 * 100),
+        v => AttemptResult::Ok(v)
+    }
+}
@@ -31,7 +31,7 @@ impl<'de> Visitor<'de> for WcaIdVisitor {
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
         where
             E: serde::de::Error, {
-        if v.len() != 10 {
+        if v.len() != 10 || !v.is_ascii() {
             return Err(E::custom(format!("WcaId too short or too long")));
         }
         let year = &v[0..4];
@@ -57,4 +57,20 @@ impl Serialize for WcaId {
             self.id);
         serializer.serialize_str(&str)
     }
-}
\ No newline at end of file
+}
+impl std::fmt::Display for WcaId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}{}{:02}",
+            self.year,
+            self.chars.iter().map(|u|*u as char).collect::<String>(),
+            self.id)
+    }
+}
+
+impl std::str::FromStr for WcaId {
+    type Err = serde::de::value::Error;
+
+    fn from_str(s: &str) -> Result<WcaId, Self::Err> {
+        WcaIdVisitor.visit_str(s)
+    }
+}
@@ -1,12 +1,15 @@
-use pdf::{run, save_pdf};
-use scorecard_to_pdf::{Language, Scorecard};
+use pdf::{run, save_pdf, Bundle, SaveToFile};
+use scorecard_to_pdf::Scorecard;
+use wca_oauth::WcifContainer;
+pub use scorecard_to_pdf::{BadgeOptions, ReportOptions, PosterOptions, ScorecardBundle, ScorecardDocument, DocumentSplit, Language, LanguageError, ScorecardOptions, PageLayout, PaperSize, Margins, CutMarks, ScorecardTemplate, TemplateError, Fonts, FontError, Image, ImageError, Weight};
 
 mod pdf;
 pub(crate) mod wcif;
 mod localhost;
 mod compiled;
 
-pub use pdf::Stages;
+pub use pdf::{Stages, StageName};
+pub use wcif::RoundError;
 
 static mut LOGGING: bool = false;
 
@@ -35,23 +38,84 @@ pub fn print_round_1_with_language<I>(args: &mut I, language: Language) where I:
     let b = args.next().unwrap();
     let b = std::fs::read_to_string(b).unwrap();
     let c = args.next().unwrap();
-    run(&a, Some(b), &c, language, Stages::new(1, u32::MAX), ScorecardOrdering::Default);
+    run(&a, Some(b), &c, language, Stages::new(1, u32::MAX), ScorecardOrdering::Default, &ScorecardOptions::default(), Bundle);
 }
 
+/// English scorecards with the default options. See `print_subsequent_rounds_with`.
 pub fn print_subsequent_rounds(competition_id: String, stages: Stages, sort_by_name: bool) {
-    localhost::init(competition_id, stages, ScorecardOrdering::from_bool(sort_by_name));
+    print_subsequent_rounds_with(competition_id, stages, sort_by_name, Language::english(), ScorecardOptions::default());
 }
 
+pub fn print_subsequent_rounds_with(competition_id: String, stages: Stages, sort_by_name: bool, language: Language, options: ScorecardOptions) {
+    localhost::init(competition_id, stages, ScorecardOrdering::from_bool(sort_by_name), language, options);
+}
+
+/// Scorecards for a round straight from the wcif, without the group menu of
+/// `print_subsequent_rounds`. The competitors advancing from the previous round are seeded into
+/// groups by their ranking, with the slowest in the first group. First rounds are seeded by
+/// personal bests. Fails if the round is not in the wcif.
+pub fn scorecards_for_round(wcif: &mut WcifContainer, event: &str, round: usize, stages: &Stages, sort_by_name: bool, language: Language, options: &ScorecardOptions) -> Result<ScorecardBundle, RoundError> {
+    pdf::run_headless(wcif, event, round, stages, ScorecardOrdering::from_bool(sort_by_name), language, options)
+}
+
+/// English scorecards with the default options. See `print_round_1_translated`.
 pub fn print_round_1_english(groups_csv: &str, limit_csv: Option<String>, competition: &str, stages: Stages, sort_by_name: bool) {
+    print_round_1_translated(groups_csv, limit_csv, competition, stages, sort_by_name, Language::english(), ScorecardOptions::default());
+}
+
+/// The QR codes of the scorecards start with `options.competition_id`, which is left empty unless
+/// it is set, as the csv files do not have it.
+pub fn print_round_1_translated(groups_csv: &str, limit_csv: Option<String>, competition: &str, stages: Stages, sort_by_name: bool, language: Language, options: ScorecardOptions) {
     let groups_csv = std::fs::read_to_string(groups_csv).unwrap();
     let limit_csv = limit_csv.map(|x| std::fs::read_to_string(x).unwrap());
     let compare = ScorecardOrdering::from_bool(sort_by_name);
-    let scorecards = run(&groups_csv, limit_csv, competition, Language::english(), stages, compare);
-    save_pdf(scorecards, competition, "").unwrap();
+    run(&groups_csv, limit_csv, competition, language, stages, compare, &options, SaveToFile { competition, prefix: "" }).unwrap();
 }
 
+/// English scorecards with the default options. See `blank_scorecard_page_with`.
 pub fn blank_scorecard_page(competition: &str) {
-    save_pdf(scorecard_to_pdf::blank_scorecard_page(competition, &Language::english()), competition, "blank_").unwrap();
+    blank_scorecard_page_with(competition, Language::english(), ScorecardOptions::default());
+}
+
+pub fn blank_scorecard_page_with(competition: &str, language: Language, options: ScorecardOptions) {
+    save_pdf(scorecard_to_pdf::blank_scorecard_page(competition, &language, &options), competition, "blank_").unwrap();
+}
+
+/// Saves name tags for all competitors and staff, with their assignments from the wcif.
+pub fn print_badges(wcif: &WcifContainer, language: Language, options: BadgeOptions) {
+    let badges = wcif::get_badges(wcif);
+    let competition = &wcif.get().name;
+    let pdf = scorecard_to_pdf::badges_to_pdf(&badges, competition, &language, &options);
+    let file_name = format!("{}_badges.pdf", competition.split_ascii_whitespace().collect::<String>());
+    std::fs::write(file_name, pdf).unwrap();
+}
+
+/// Saves wall sheets with the groups and staff of every round which has groups in the wcif.
+pub fn print_assignment_sheets(wcif: &WcifContainer, language: Language, options: ReportOptions) {
+    let rounds: Vec<_> = wcif.events_iter()
+        .flat_map(|event| (1..=event.rounds.len()).map(move |round| (event.id.clone(), round)))
+        .filter_map(|(event, round)| wcif::get_round_assignments(wcif, &event, round))
+        .collect();
+    let competition = &wcif.get().name;
+    let pdf = scorecard_to_pdf::assignment_sheets_to_pdf(&rounds, competition, &wcif::get_id_map(wcif), &language, &options);
+    let file_name = format!("{}_assignments.pdf", competition.split_ascii_whitespace().collect::<String>());
+    std::fs::write(file_name, pdf).unwrap();
+}
+
+pub fn print_personal_schedules(wcif: &WcifContainer, language: Language, options: ReportOptions) {
+    let schedules = wcif::get_personal_schedules(wcif);
+    let competition = &wcif.get().name;
+    let pdf = scorecard_to_pdf::personal_schedules_to_pdf(&schedules, competition, &language, &options);
+    let file_name = format!("{}_schedules.pdf", competition.split_ascii_whitespace().collect::<String>());
+    std::fs::write(file_name, pdf).unwrap();
+}
+
+pub fn print_schedule_poster(wcif: &WcifContainer, language: Language, options: PosterOptions) {
+    let rooms = wcif::get_poster_rooms(wcif);
+    let competition = &wcif.get().name;
+    let pdf = scorecard_to_pdf::schedule_poster_to_pdf(&rooms, competition, &language, &options);
+    let file_name = format!("{}_schedule_poster.pdf", competition.split_ascii_whitespace().collect::<String>());
+    std::fs::write(file_name, pdf).unwrap();
 }
 
 #[derive(Clone, Copy)]
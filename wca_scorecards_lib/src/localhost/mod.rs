@@ -1,5 +1,6 @@
 use std::{sync::Arc, collections::HashMap, net::SocketAddr};
 use crate::{wcif::*, Stages, ScorecardOrdering};
+use scorecard_to_pdf::{Language, ScorecardOptions};
 use tokio::sync::Mutex;
 use warp::{Filter, hyper::Response, Rejection};
 use wca_oauth::WcifOAuth;
@@ -15,7 +16,7 @@ pub use responses::is_localhost;
 type DB = Arc<Mutex<Option<WcifOAuth>>>;
 
 #[tokio::main]
-pub(crate) async fn init(id: String, stages: Stages, compare: ScorecardOrdering) {
+pub(crate) async fn init(id: String, stages: Stages, compare: ScorecardOrdering, language: Language, options: ScorecardOptions) {
     //Url to approve the Oauth application
     let auth_url = "https://www.worldcubeassociation.org/oauth/authorize?client_id=nqbnCQGGO605D_XYpgghZdIN2jDT67LhhUC1kE-Msuk&redirect_uri=http%3A%2F%2Flocalhost%3A5000%2F&response_type=token&scope=public+manage_competitions";
 
@@ -52,7 +53,9 @@ pub(crate) async fn init(id: String, stages: Stages, compare: ScorecardOrdering)
         .and_then(move |query: HashMap<String, String>, socket: Option<SocketAddr>|{
             let wcif = local_wcif.clone();
             let stages = stages.clone();
-            pdf(wcif, query, socket, stages, compare)
+            let language = language.clone();
+            let options = options.clone();
+            pdf(wcif, query, socket, stages, compare, language, options)
         });
 
     let wasm_js = warp::path!("round" / "pkg" / "group_menu.js")
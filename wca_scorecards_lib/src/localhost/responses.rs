@@ -4,7 +4,7 @@ use super::DB;
 
 use crate::read_logging;
 
-use scorecard_to_pdf::Return;
+use scorecard_to_pdf::OutputKind;
 use wca_oauth::{Assignment, AssignmentCode};
 
 pub fn is_localhost(socket: Option<SocketAddr>) -> Result<(), Rejection> {
@@ -68,7 +68,7 @@ pub async fn round(db: DB, query: HashMap<String, String>, socket: Option<Socket
     let round = usize::from_str_radix(&query["round"], 10).unwrap();
     let mut db_guard = db.lock().await;
     let wcif = (*db_guard).as_mut().unwrap();
-    let (competitors, map) = crate::wcif::get_competitors_for_round(wcif, eventid, round);
+    let (competitors, map) = crate::wcif::get_competitors_for_round(wcif, eventid, round).map_err(|_| warp::reject())?;
     drop(db_guard);
     let str = competitors.iter()
         .rev()
@@ -83,23 +83,7 @@ pub async fn round(db: DB, query: HashMap<String, String>, socket: Option<Socket
         .map_err(|_| warp::reject())
 }
 
-pub(crate) async fn pdf(db: DB, query: HashMap<String, String>, socket: Option<SocketAddr>, stages: Stages, compare: ScorecardOrdering) -> Result<Response<Vec<u8>>, Rejection> {
-    fn assign_stages(groups: Vec<Vec<usize>>, stages: &Stages) -> Vec<Vec<(usize, usize)>> {
-        groups.into_iter()
-            .map(|group| {
-                    let no_of_stages = (group.len() + stages.capacity as usize - 1) / stages.capacity as usize;
-                    let lower_per_stage = group.len() / no_of_stages;
-                    let leftover = group.len() - lower_per_stage * no_of_stages;
-                    let splits = (0..no_of_stages).map(|i| lower_per_stage * i + i.min(leftover));
-                    group.into_iter().enumerate().map(|(idx, id)| {
-                        let (stage, lower) = splits.clone().enumerate().rev().find(|(_, lower)| *lower <= idx).expect("First is 0");
-                        let station = stages.capacity as usize * stage + idx - lower + 1;
-                        (id, station)
-                    }).collect()  
-                })
-            .collect()
-    }
-    
+pub(crate) async fn pdf(db: DB, query: HashMap<String, String>, socket: Option<SocketAddr>, stages: Stages, compare: ScorecardOrdering, language: Language, options: ScorecardOptions) -> Result<Response<Vec<u8>>, Rejection> {
     is_localhost(socket)?;
     let eventid = &query["eventid"];
     let round = query["round"].parse().unwrap();
@@ -115,7 +99,7 @@ pub(crate) async fn pdf(db: DB, query: HashMap<String, String>, socket: Option<S
         })
         .collect();
 
-    let groups_with_stations = assign_stages(groups.clone(), &stages);
+    let groups_with_stations = crate::pdf::assign_stages(groups.clone(), &stages);
 
     let wcif_oauth = wcif_oauth.as_mut().unwrap();
     if wcif {
@@ -139,20 +123,20 @@ pub(crate) async fn pdf(db: DB, query: HashMap<String, String>, socket: Option<S
         }
     }
 
-    let bytes = crate::pdf::run_from_wcif(wcif_oauth, eventid, round, groups_with_stations, &stages, compare);
-
-    match bytes {
-        Return::Pdf(bytes) => {
-            Response::builder()
-                .header("content-type", "application/pdf")
-                .body(bytes)
-                .map_err(|_| warp::reject())
-        }
-        Return::Zip(bytes) => {
-            Response::builder()
-                .header("content-type", "application/zip")
-                .body(bytes)
-                .map_err(|_| warp::reject())
+    let mut bytes = vec![];
+    let written = crate::pdf::run_from_wcif(wcif_oauth, eventid, round, groups_with_stations, &stages, compare, language, &options, crate::pdf::Stream(&mut bytes));
+    let content_type = match written {
+        Ok((kind, warnings)) => {
+            crate::pdf::print_warnings(&warnings);
+            match kind {
+                OutputKind::Pdf => "application/pdf",
+                OutputKind::Zip => "application/zip",
+            }
         }
-    }
+        Err(_) => return Err(warp::reject())
+    };
+    Response::builder()
+        .header("content-type", content_type)
+        .body(bytes)
+        .map_err(|_| warp::reject())
 }
\ No newline at end of file
@@ -1,40 +1,151 @@
-use std::{collections::HashMap, io::Write};
+use std::collections::HashMap;
 use std::fs::File;
 use crate::ScorecardOrdering;
-use crate::wcif::get_round_json;
-use scorecard_to_pdf::{Scorecard, TimeLimit, scorecards_to_pdf, Language};
+use crate::wcif::{get_round_json, RoundError};
+use std::io::Write;
+use scorecard_to_pdf::{Scorecard, Stage, TimeLimit, scorecards_to_pdf, write_scorecards, Language, ScorecardOptions};
 use wca_oauth::WcifContainer;
-use scorecard_to_pdf::Return;
+use scorecard_to_pdf::{ScorecardBundle, OutputKind, TruncatedText};
 
 #[derive(Clone)]
 pub struct Stages {
     pub(crate) no: u32,
     pub(crate) capacity: u32,
+    names: Vec<StageName>,
+}
+
+/// Name and colour of a stage, printed on its scorecards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StageName {
+    pub name: String,
+    /// Colour as hex, e.g. `#c0392b`.
+    pub colour: Option<String>,
 }
 
 impl Stages {
     pub fn new(no: u32, capacity: u32) -> Stages {
-        Stages { no, capacity }
+        Stages { no, capacity, names: vec![] }
+    }
+
+    /// Names the stages in order. Stages without a name are shown by their number. Scorecards made
+    /// from the wcif are otherwise named after the rooms of the round, if there is a room per stage.
+    pub fn with_names(mut self, names: Vec<StageName>) -> Stages {
+        self.names = names;
+        self
+    }
+
+    /// Stages from the sub-stages of a `stage` command of the group settings, e.g. `Red-20 Blue-20`,
+    /// given as their names and number of stations. Stages of different sizes are only filled up to
+    /// the size of the smallest.
+    pub fn from_sub_stages(sub_stages: &[(Option<String>, usize)]) -> Stages {
+        let capacity = sub_stages.iter().map(|(_, size)| *size).min().map_or(u32::MAX, |size| size.clamp(1, u32::MAX as usize) as u32);
+        let names = sub_stages.iter()
+            .enumerate()
+            .map(|(idx, (name, _))| StageName { name: name.clone().unwrap_or_else(|| (idx + 1).to_string()), colour: None })
+            .collect();
+        Stages::new(sub_stages.len().max(1) as u32, capacity).with_names(names)
+    }
+
+    /// The stage of a station, where stations are numbered from 1, so station `capacity` is the
+    /// last one of the first stage.
+    pub(crate) fn stage(&self, station: usize) -> Stage<'_> {
+        let number = (station.saturating_sub(1) as u64 / self.capacity as u64) as u32;
+        match self.names.get(number as usize) {
+            Some(name) => Stage { number, name: Some(&name.name), colour: name.colour.as_deref() },
+            None => Stage::new(number)
+        }
     }
 }
 
-pub fn save_pdf(data: Return, competition: &str, prefix: &str) -> std::io::Result<()> {
-    let (data, name) = match data {
-        Return::Pdf(b) => (b, ".pdf"),
-        Return::Zip(b) => (b, ".zip")
-    };
-    let file_name = format!("{prefix}{}_scorecards{name}", competition.split_ascii_whitespace().collect::<String>());
-    let mut file = File::create(file_name)?;
-    file.write_all(&data)?;
-    Ok(())
+/// What is made of the scorecards of a run: a bundle with every document, or the documents written
+/// out one at a time.
+pub(crate) trait ScorecardOutput {
+    type Output;
+
+    fn output(self, scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: Language, options: &ScorecardOptions) -> Self::Output;
 }
 
-pub(crate) fn run(groups_csv: &str, limit_csv: Option<String>, competition: &str, language: Language, stages: Stages, compare: ScorecardOrdering) -> Return {
+/// Keeps every document in memory, for callers who want to look at them.
+pub(crate) struct Bundle;
+
+impl ScorecardOutput for Bundle {
+    type Output = ScorecardBundle;
+
+    fn output(self, scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: Language, options: &ScorecardOptions) -> ScorecardBundle {
+        scorecards_to_pdf(scorecards, competition, map, limits, language, options)
+    }
+}
+
+/// Writes the documents to the writer as they are drawn, so only one is in memory at a time.
+pub(crate) struct Stream<W: Write>(pub W);
+
+impl<W: Write> ScorecardOutput for Stream<W> {
+    type Output = std::io::Result<(OutputKind, Vec<TruncatedText>)>;
+
+    fn output(self, scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: Language, options: &ScorecardOptions) -> Self::Output {
+        write_scorecards(scorecards, competition, map, limits, &language, options, self.0)
+    }
+}
+
+/// Saves the scorecards as they are drawn, as a pdf for a single document and a zip for several.
+/// Nothing is left behind if writing fails.
+pub(crate) struct SaveToFile<'a> {
+    pub competition: &'a str,
+    pub prefix: &'a str,
+}
+
+impl ScorecardOutput for SaveToFile<'_> {
+    type Output = std::io::Result<()>;
+
+    fn output(self, scorecards: Vec<Scorecard>, competition: &str, map: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, language: Language, options: &ScorecardOptions) -> Self::Output {
+        //Whether it is a pdf or a zip is only known once it is written.
+        let name = format!("{}{}_scorecards", self.prefix, self.competition.split_ascii_whitespace().collect::<String>());
+        let partial = format!("{name}.part");
+        let written = File::create(&partial)
+            .and_then(|file| Stream(file).output(scorecards, competition, map, limits, language, options))
+            .and_then(|(kind, warnings)| {
+                print_warnings(&warnings);
+                std::fs::rename(&partial, format!("{name}.{}", extension(kind)))
+            });
+        if written.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        written
+    }
+}
+
+fn extension(kind: OutputKind) -> &'static str {
+    match kind {
+        OutputKind::Pdf => "pdf",
+        OutputKind::Zip => "zip"
+    }
+}
+
+/// Saves a single document as a pdf and several as a zip. Nothing is left behind if writing fails.
+pub fn save_pdf(bundle: ScorecardBundle, competition: &str, prefix: &str) -> std::io::Result<()> {
+    print_warnings(&bundle.warnings);
+    let file_name = format!("{prefix}{}_scorecards.{}", competition.split_ascii_whitespace().collect::<String>(), extension(bundle.output_kind()));
+    let written = bundle.write(File::create(&file_name)?);
+    if written.is_err() {
+        let _ = std::fs::remove_file(&file_name);
+    }
+    written.map(|_| ())
+}
+
+/// Lets the organiser know which scorecards have text cut off, so they can be fixed by hand.
+pub(crate) fn print_warnings(warnings: &[TruncatedText]) {
+    for warning in warnings {
+        println!("Text was cut off on the scorecard of {} for {} round {} group {}: {}", warning.id, warning.event, warning.round, warning.group, warning.text);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run<O: ScorecardOutput>(groups_csv: &str, limit_csv: Option<String>, competition: &str, language: Language, stages: Stages, compare: ScorecardOrdering, options: &ScorecardOptions, output: O) -> O::Output {
     let mut groups_csv = groups_csv.lines();
     //Header describing csv file formatting. First two are fixed and therfore skipped.
     //Unwrap cannot fail because the first element of lines always exists, although skip can lead
     //to panic later when used.
-    let header = groups_csv.next().unwrap().split(",").skip(2);
+    let header = groups_csv.next().unwrap().split(",").skip(2).map(event_column);
     let mut map = HashMap::new();
     let mut k = groups_csv
         //Filter off empty lines. Fixes annoying EOF issues.
@@ -60,7 +171,7 @@ pub(crate) fn run(groups_csv: &str, limit_csv: Option<String>, competition: &str
             map.insert(id, name.to_string());
             //Zipping with header (clone) to know the order of events.
             iter.zip(header.clone())
-                .filter_map(move |(asign, event)|{
+                .filter_map(move |(asign, (event, round))|{
                 //Test whether competitor is assigned.
                 if asign == "" {
                     return None
@@ -76,19 +187,20 @@ pub(crate) fn run(groups_csv: &str, limit_csv: Option<String>, competition: &str
                         Err(_) => panic!("Station number for event {} in line {} is not a positive integer", event, line + 2),
                         Ok(v) => v
                     });
-                    Some((id, event, group, station))
+                    Some((id, event, round, group, station))
                 }
             })
         })
         .flatten()
-        .map(|(id, event, group, station)|{
+        .map(|(id, event, round, group, station)|{
             Scorecard {
                 id,
                 group,
-                round: 1,
+                round,
                 station,
                 event,
-                stage: station.map(|x| x as u32 / stages.capacity),
+                stage: station.map(|station| stages.stage(station)),
+                format: None,
             }
         })
         .collect::<Vec<_>>();
@@ -107,22 +219,23 @@ pub(crate) fn run(groups_csv: &str, limit_csv: Option<String>, competition: &str
             }.split(",");
 
             let mut limits = HashMap::new();
-            limit_data.zip(event_list).for_each(|(x, event)|{
+            limit_data.zip(event_list).for_each(|(x, column)|{
+                let key = event_column(column);
                 let mut iter = x.split(";");
                 let v = match iter.next() {
                     None => {
-                        limits.insert(event, TimeLimit::None);
+                        limits.insert(key, TimeLimit::None);
                         return;
                     }
                     Some(v) => v,
                 };
                 match v {
-                    "T" => limits.insert(event, TimeLimit::Single(usize_from_iter(&mut iter))),
-                    "C" => limits.insert(event, TimeLimit::Cumulative(usize_from_iter(&mut iter))),
-                    "K" => limits.insert(event, TimeLimit::Cutoff(usize_from_iter(&mut iter), usize_from_iter(&mut iter))),
-                    "S" => limits.insert(event, TimeLimit::SharedCumulative(usize_from_iter(&mut iter), iter.map(|x|x.to_string()).collect::<Vec<_>>())),
-                    "M" => limits.insert(event, TimeLimit::Multi),
-                    _ => panic!("Malformatted time limit for event: {}", event)
+                    "T" => limits.insert(key, TimeLimit::Single(usize_from_iter(&mut iter))),
+                    "C" => limits.insert(key, TimeLimit::Cumulative(usize_from_iter(&mut iter))),
+                    "K" => limits.insert(key, TimeLimit::Cutoff(usize_from_iter(&mut iter), usize_from_iter(&mut iter))),
+                    "S" => limits.insert(key, TimeLimit::SharedCumulative(usize_from_iter(&mut iter), iter.map(|x|x.to_string()).collect::<Vec<_>>())),
+                    "M" => limits.insert(key, TimeLimit::Multi),
+                    _ => panic!("Malformatted time limit for event: {}", column)
                 };
             });
             limits
@@ -132,16 +245,72 @@ pub(crate) fn run(groups_csv: &str, limit_csv: Option<String>, competition: &str
     
 
     //Generate pdf
-    scorecards_to_pdf(k, competition, &map, &limits, language)
+    output.output(k, competition, &map, &limits, language, options)
 }
 
-pub(crate) fn run_from_wcif(wcif: &mut WcifContainer, event: &str, round: usize, groups: Vec<Vec<(usize, usize)>>, stages: &Stages, compare: ScorecardOrdering) -> Return {
+/// Gives every competitor a station. Groups too large for a single stage are spread evenly over as
+/// few stages as possible.
+pub(crate) fn assign_stages(groups: Vec<Vec<usize>>, stages: &Stages) -> Vec<Vec<(usize, usize)>> {
+    groups.into_iter()
+        .map(|group| {
+                let no_of_stages = (group.len() + stages.capacity as usize - 1) / stages.capacity as usize;
+                let lower_per_stage = group.len() / no_of_stages;
+                let leftover = group.len() - lower_per_stage * no_of_stages;
+                let splits = (0..no_of_stages).map(|i| lower_per_stage * i + i.min(leftover));
+                group.into_iter().enumerate().map(|(idx, id)| {
+                    let (stage, lower) = splits.clone().enumerate().rev().find(|(_, lower)| *lower <= idx).expect("First is 0");
+                    let station = stages.capacity as usize * stage + idx - lower + 1;
+                    (id, station)
+                }).collect()
+            })
+        .collect()
+}
+
+/// Splits competitors, given best first, into groups as the group menu does: as few groups as fit
+/// on all stages, with the slowest competitors in the first group. Within a group the best come
+/// first.
+pub(crate) fn seed_groups(competitors: &[usize], stages: &Stages) -> Vec<Vec<usize>> {
+    if competitors.is_empty() {
+        return vec![];
+    }
+    let group_size = (stages.capacity as usize).saturating_mul(stages.no as usize).max(1);
+    let no_of_groups = competitors.len().div_ceil(group_size);
+    let lower_group_size = competitors.len() / no_of_groups;
+    let leftover = competitors.len() % no_of_groups;
+    let mut slowest_first = competitors.iter().rev().copied();
+    (0..no_of_groups)
+        .map(|group| {
+            let size = lower_group_size + usize::from(group < leftover);
+            let mut group: Vec<usize> = slowest_first.by_ref().take(size).collect();
+            group.reverse();
+            group
+        })
+        .collect()
+}
+
+/// Scorecards for a round without the group menu. The competitors advancing from the previous round,
+/// or registered by their personal bests for first rounds, are seeded into groups and get stations
+/// stage by stage.
+pub(crate) fn run_headless(wcif: &mut WcifContainer, event: &str, round: usize, stages: &Stages, compare: ScorecardOrdering, language: Language, options: &ScorecardOptions) -> Result<ScorecardBundle, RoundError> {
+    let (competitors, _) = crate::wcif::get_competitors_for_round(wcif, event, round)?;
+    let groups = assign_stages(seed_groups(&competitors, stages), stages);
+    Ok(run_from_wcif(wcif, event, round, groups, stages, compare, language, options, Bundle))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_from_wcif<O: ScorecardOutput>(wcif: &mut WcifContainer, event: &str, round: usize, groups: Vec<Vec<(usize, usize)>>, stages: &Stages, compare: ScorecardOrdering, language: Language, options: &ScorecardOptions, output: O) -> O::Output {
     let (map, limit, competition) = crate::wcif::get_scorecard_info_for_round(wcif, event, round);
+    let rooms = crate::wcif::get_round_rooms(wcif, event, round);
+    let stages = &match stages.names.is_empty() && rooms.len() == stages.no as usize {
+        true => stages.clone().with_names(rooms),
+        false => stages.clone()
+    };
+    let format = crate::wcif::get_round_format(wcif, event, round);
 
     //Unwrap should not fail as the existence of this round is already confirmed at this point.
     get_round_json(wcif, event, round).unwrap().scramble_set_count = groups.len();
     let mut limits = HashMap::new();
-    limits.insert(event, limit);
+    limits.insert((event, round), limit);
 
     let mut k = groups.into_iter()
         .zip(1..)
@@ -154,7 +323,8 @@ pub(crate) fn run_from_wcif(wcif: &mut WcifContainer, event: &str, round: usize,
                         group: n,
                         station: Some(station),
                         id,
-                        stage: Some(station as u32 / stages.capacity),
+                        stage: Some(stages.stage(station)),
+                        format,
                     }
                 })
         }).flatten()
@@ -162,7 +332,21 @@ pub(crate) fn run_from_wcif(wcif: &mut WcifContainer, event: &str, round: usize,
 
     compare.sort_slice(&mut k);
     
-    scorecards_to_pdf(k, &competition, &map, &limits, Language::english())
+    //The QR codes need the competition id, which is known from the wcif.
+    let options = ScorecardOptions { competition_id: wcif.get().id.clone(), ..options.clone() };
+    output.output(k, &competition, &map, &limits, language, &options)
+}
+
+/// The event and round of a column in the groups or time limit csv. Columns are either an event id
+/// for its first round, e.g. `333`, or an event id with a round, e.g. `333-r2`.
+fn event_column(column: &str) -> (&str, usize) {
+    match column.rsplit_once("-r") {
+        Some((event, round)) => match round.parse() {
+            Ok(round) => (event, round),
+            Err(_) => panic!("Malformatted round in csv column: {}", column)
+        }
+        None => (column, 1)
+    }
 }
 
 fn usize_from_iter<'a, I>(iter: &mut I) -> usize where I: Iterator<Item = &'a str> {
@@ -174,3 +358,83 @@ fn usize_from_iter<'a, I>(iter: &mut I) -> usize where I: Iterator<Item = &'a st
         Ok(v) => v
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use scorecard_to_pdf::{Language, Scorecard, ScorecardOptions, TimeLimit};
+    use crate::ScorecardOrdering;
+    use super::{assign_stages, event_column, run, seed_groups, ScorecardOutput, Stages};
+
+    #[test]
+    fn seeding() {
+        let stages = Stages::new(2, 3);
+        assert!(seed_groups(&[], &stages).is_empty());
+        assert_eq!(seed_groups(&[1, 2, 3, 4, 5], &stages), vec![vec![1, 2, 3, 4, 5]]);
+        //Fourteen competitors on six stations is three groups, with the slowest first.
+        let competitors: Vec<_> = (1..=14).collect();
+        assert_eq!(seed_groups(&competitors, &stages), vec![
+            vec![10, 11, 12, 13, 14],
+            vec![5, 6, 7, 8, 9],
+            vec![1, 2, 3, 4],
+        ]);
+    }
+
+    #[test]
+    fn stations() {
+        let stages = Stages::new(2, 3);
+        //Groups fitting on a stage stay on the first stage, larger ones are spread evenly.
+        assert_eq!(assign_stages(vec![vec![7, 8]], &stages), vec![vec![(7, 1), (8, 2)]]);
+        assert_eq!(assign_stages(vec![vec![1, 2, 3, 4, 5]], &stages), vec![vec![(1, 1), (2, 2), (3, 3), (4, 4), (5, 5)]]);
+        assert_eq!(assign_stages(vec![vec![1, 2, 3, 4]], &stages), vec![vec![(1, 1), (2, 2), (3, 4), (4, 5)]]);
+        assert_eq!(stages.stage(3).number, 0);
+        assert_eq!(stages.stage(4).number, 1);
+    }
+
+    #[test]
+    fn event_columns() {
+        assert_eq!(event_column("333"), ("333", 1));
+        assert_eq!(event_column("333bf-r1"), ("333bf", 1));
+        assert_eq!(event_column("444-r2"), ("444", 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Malformatted round")]
+    fn malformed_event_column() {
+        event_column("333-rtwo");
+    }
+
+    /// The event, round and single time limit of every scorecard.
+    struct Limits;
+
+    impl ScorecardOutput for Limits {
+        type Output = Vec<(String, usize, Option<usize>)>;
+
+        fn output(self, scorecards: Vec<Scorecard>, _: &str, _: &HashMap<usize, String>, limits: &HashMap<(&str, usize), TimeLimit>, _: Language, _: &ScorecardOptions) -> Self::Output {
+            scorecards.iter()
+                .map(|scorecard| {
+                    let limit = match limits.get(&(scorecard.event, scorecard.round)) {
+                        Some(TimeLimit::Single(limit)) => Some(*limit),
+                        _ => None
+                    };
+                    (scorecard.event.to_string(), scorecard.round, limit)
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn later_round_columns() {
+        let limits = "333,333-r2,444-r2\nT;60000,T;30000,T;90000".to_string();
+        let scorecards = run("Name,Id,333-r2,444\nA,1,1,2", Some(limits), "Test", Language::english(), Stages::new(1, 10), ScorecardOrdering::Default, &ScorecardOptions::default(), Limits);
+        assert_eq!(scorecards, vec![("333".to_string(), 2, Some(30000)), ("444".to_string(), 1, None)]);
+    }
+
+    #[test]
+    fn sub_stages() {
+        let stages = Stages::from_sub_stages(&[(Some("Red".to_string()), 20), (None, 16)]);
+        assert_eq!((stages.no, stages.capacity), (2, 16));
+        assert_eq!(stages.stage(16).label(), "Red");
+        assert_eq!(stages.stage(17).label(), "2");
+    }
+}
@@ -1,8 +1,12 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 
 use wca_oauth::*;
 
-use scorecard_to_pdf::TimeLimit;
+use scorecard_to_pdf::{Badge, BadgeAssignment, BadgeRole, GroupAssignments, PersonalSchedule, PosterActivity, PosterRoom, RoundAssignments, RoundFormat, ScheduleEntry, StaffTask, TimeLimit};
+use chrono::{TimeZone, Timelike};
+use crate::StageName;
+use chrono_tz::Tz;
 
 pub fn get_rounds(wcif: &mut WcifContainer) -> Vec<(String, usize)> {
     wcif.events_iter()
@@ -40,71 +44,335 @@ pub fn get_time_limit(wcif: &mut WcifContainer, event: &str, round: usize) -> Ti
     }
 }
 
-pub fn get_competitors_for_round(wcif: &mut WcifContainer, event: &str, round: usize) -> (Vec<usize>, HashMap<usize, String>) {
-    let id_map = get_id_map(wcif);
-    let round_json = get_round_json(wcif, event, round - 1);
-    let advancement_ids = match round_json {
-        Some(v) => get_advancement_ids(v, &v.advancement_condition),
-        None => {
-            wcif.persons_iter().filter_map(|p|{
-                let reg = p.registration.as_ref()?;
-                if reg.status == format!("accepted") && reg.event_ids.contains(&event.to_string()) {
-                    Some(p.registrant_id.unwrap())
-                } else { None }
-            }).collect()
+pub fn get_round_format(wcif: &mut WcifContainer, event: &str, round: usize) -> Option<RoundFormat> {
+    let round_json = get_round_json(wcif, event, round)?;
+    RoundFormat::from_wcif(round_json.format, round_json.cutoff.as_ref().map(|cutoff| cutoff.number_of_attempts))
+}
+
+/// Badges for everyone with an accepted registration or a role at the competition. Assignments are
+/// listed in the order of the schedule.
+pub fn get_badges(wcif: &WcifContainer) -> Vec<Badge> {
+    let mut activities: Vec<_> = wcif.activity_iter().collect();
+    activities.sort_by_key(|activity| activity.start_time);
+    let mut persons: Vec<_> = wcif.persons_iter()
+        .filter(|p| p.registration.as_ref().is_some_and(|reg| reg.status == "accepted") || !p.roles.is_empty())
+        .collect();
+    persons.sort_by(|a, b| a.name.cmp(&b.name));
+    persons.into_iter().map(|person| {
+        let mut assignments: Vec<BadgeAssignment> = vec![];
+        for activity in &activities {
+            let assignment = match person.assignments.iter().find(|a| a.activity_id == activity.id) {
+                None => continue,
+                Some(v) => v
+            };
+            let (event, round, group) = match parse_group_code(&activity.activity_code) {
+                None => continue,
+                Some(v) => v
+            };
+            let idx = match assignments.iter().position(|a| a.event == event && a.round == round) {
+                Some(v) => v,
+                None => {
+                    assignments.push(BadgeAssignment { event: event.to_string(), round, competing: None, staff: vec![] });
+                    assignments.len() - 1
+                }
+            };
+            match staff_task(&assignment.assignment_code) {
+                None => assignments[idx].competing = Some(group),
+                Some(task) => assignments[idx].staff.push((task, group)),
+            }
         }
+        let role = if person.roles.contains(&Role::Delegate) {
+            BadgeRole::Delegate
+        } else if person.roles.contains(&Role::TraineeDelegate) {
+            BadgeRole::TraineeDelegate
+        } else if person.roles.contains(&Role::Organizer) {
+            BadgeRole::Organizer
+        } else if !person.roles.is_empty() || assignments.iter().any(|a| !a.staff.is_empty()) {
+            BadgeRole::Staff
+        } else {
+            BadgeRole::Competitor
+        };
+        Badge {
+            name: person.name.clone(),
+            wca_id: person.wca_id.as_ref().map(|id| id.to_string()),
+            country: country_name(&person.country_iso_2).unwrap_or(&person.country_iso_2).to_string(),
+            role,
+            assignments,
+        }
+    }).collect()
+}
+
+/// The groups of a round with their competitors and staff, if the round has groups in the schedule.
+pub fn get_round_assignments(wcif: &WcifContainer, event: &str, round: usize) -> Option<RoundAssignments> {
+    let mut groups: Vec<(usize, usize)> = wcif.activity_iter()
+        .filter_map(|activity| match parse_group_code(&activity.activity_code) {
+            Some((e, r, group)) if e == event && r == round && group > 0 => Some((activity.id, group)),
+            _ => None
+        })
+        .collect();
+    if groups.is_empty() {
+        return None;
+    }
+    //Groups with the same number in different rooms are merged.
+    groups.sort_by_key(|(_, group)| *group);
+    let mut round_assignments = RoundAssignments {
+        event: event.to_string(),
+        round,
+        groups: vec![GroupAssignments::default(); groups.last().unwrap().1],
     };
-    (advancement_ids, id_map)
+    for person in wcif.persons_iter() {
+        let id = match person.registrant_id {
+            None => continue,
+            Some(v) => v
+        };
+        for assignment in &person.assignments {
+            let group = match groups.iter().find(|(activity, _)| *activity == assignment.activity_id) {
+                None => continue,
+                Some((_, group)) => &mut round_assignments.groups[group - 1]
+            };
+            match staff_task(&assignment.assignment_code) {
+                None => group.competitors.push((id, assignment.station_number)),
+                Some(task) => group.staff.push((id, task)),
+            }
+        }
+    }
+    Some(round_assignments)
 }
 
-pub(crate) fn get_round_json<'a>(wcif: &'a mut WcifContainer, event: &str, round: usize) -> Option<&'a mut Round> {
-    let activity_id = format!("{}-r{}", event, round);
-    wcif.round_iter_mut().find(|round| round.id == activity_id)
+/// The assignments of every accepted competitor and staff member, with times in the local time of
+/// the venue the activity takes place in.
+pub fn get_personal_schedules(wcif: &WcifContainer) -> Vec<PersonalSchedule> {
+    let mut activities: Vec<(&Activity, &Room, Tz)> = vec![];
+    for venue in &wcif.get().schedule.venues {
+        //Unknown time zones are printed in UTC rather than not at all.
+        let timezone = venue.timezone.parse().unwrap_or(Tz::UTC);
+        for room in &venue.rooms {
+            collect_activities(&room.activities, room, timezone, &mut activities);
+        }
+    }
+    activities.sort_by_key(|(activity, _, _)| activity.start_time);
+
+    wcif.persons_iter()
+        .filter(|p| p.registration.as_ref().is_some_and(|reg| reg.status == "accepted") || !p.roles.is_empty())
+        .map(|person| {
+            let entries = activities.iter()
+                .filter_map(|(activity, room, timezone)| {
+                    let assignment = person.assignments.iter().find(|a| a.activity_id == activity.id)?;
+                    let (event, round, group) = parse_group_code(&activity.activity_code)?;
+                    let start = timezone.from_utc_datetime(&activity.start_time);
+                    let end = timezone.from_utc_datetime(&activity.end_time);
+                    Some(ScheduleEntry {
+                        day: start.format("%Y-%m-%d").to_string(),
+                        start: start.format("%H:%M").to_string(),
+                        end: end.format("%H:%M").to_string(),
+                        event: event.to_string(),
+                        round,
+                        group,
+                        task: staff_task(&assignment.assignment_code),
+                        room: room.name.clone(),
+                        room_colour: room.color.clone(),
+                    })
+                })
+                .collect();
+            PersonalSchedule {
+                name: person.name.clone(),
+                wca_id: person.wca_id.as_ref().map(|id| id.to_string()),
+                entries,
+            }
+        })
+        .collect()
+}
+
+/// Every room of the competition with its activities, with times in the local time of its venue.
+pub fn get_poster_rooms(wcif: &WcifContainer) -> Vec<PosterRoom> {
+    wcif.get().schedule.venues.iter()
+        .flat_map(|venue| {
+            let timezone = venue.timezone.parse().unwrap_or(Tz::UTC);
+            venue.rooms.iter().map(move |room| {
+                let mut activities: Vec<_> = room.activities.iter().map(|activity| poster_activity(activity, timezone)).collect();
+                activities.sort_by_key(|activity| (activity.day.clone(), activity.start));
+                PosterRoom { name: room.name.clone(), colour: room.color.clone(), activities }
+            })
+        })
+        .collect()
+}
+
+fn poster_activity(activity: &Activity, timezone: Tz) -> PosterActivity {
+    let start = timezone.from_utc_datetime(&activity.start_time);
+    let end = timezone.from_utc_datetime(&activity.end_time);
+    let minutes = |time: &chrono::DateTime<Tz>| time.hour() * 60 + time.minute();
+    //Activities running past midnight are cut off at the end of the day.
+    let end = if end.date_naive() == start.date_naive() { minutes(&end) } else { 24 * 60 };
+    PosterActivity {
+        name: activity.name.clone(),
+        activity_code: activity.activity_code.clone(),
+        day: start.format("%Y-%m-%d").to_string(),
+        start: minutes(&start),
+        end,
+        children: activity.child_activities.iter().map(|child| poster_activity(child, timezone)).collect(),
+    }
+}
+
+fn collect_activities<'a>(activities: &'a [Activity], room: &'a Room, timezone: Tz, result: &mut Vec<(&'a Activity, &'a Room, Tz)>) {
+    for activity in activities {
+        result.push((activity, room, timezone));
+        collect_activities(&activity.child_activities, room, timezone, result);
+    }
+}
+
+/// Splits the activity code of a group, e.g. 333-r1-g2, into event, round and group.
+fn parse_group_code(code: &str) -> Option<(&str, usize, usize)> {
+    let mut code = code.split('-');
+    let event = code.next()?;
+    let round = code.next()?.strip_prefix('r')?.parse().ok()?;
+    let group = code.next()?.strip_prefix('g')?.parse().ok()?;
+    Some((event, round, group))
 }
 
-fn get_advancement_amount(round: &Round, advancement_condition: &Option<AdvancementCondition>) -> Option<usize> {
-    let number_of_competitors = round.results.len();
-    match advancement_condition {
-        None => None,
-        Some(v) => Some( match v {
-            AdvancementCondition::Percent(level) => number_of_competitors * level / 100,
-            AdvancementCondition::Ranking(level) => *level,
-            AdvancementCondition::AttemptResult(level) => {
-                let mut intermediate = round.results.iter().collect::<Vec<_>>();
-                intermediate.sort_by_key(|r| r.ranking);
-                let x = intermediate.into_iter().enumerate().find(|(_, result)|{
-                    match result.average {
-                        AttemptResult::DNF | AttemptResult::DNS | AttemptResult::Skip => true,
-                        AttemptResult::Ok(average) => average as usize > *level
-                    }
-                }).map(|(x, _)| x);
-                let percent = get_advancement_amount(round, &Some(AdvancementCondition::Percent(75))).unwrap();
-                match x {
-                    Some(v) if v < percent => v,
-                    _ => percent
+/// The staff task of an assignment, or none when competing.
+fn staff_task(code: &AssignmentCode) -> Option<StaffTask> {
+    Some(match code {
+        AssignmentCode::Competitor => return None,
+        AssignmentCode::Judge => StaffTask::Judge,
+        AssignmentCode::Scrambler => StaffTask::Scrambler,
+        AssignmentCode::Runner => StaffTask::Runner,
+        AssignmentCode::DataEntry => StaffTask::DataEntry,
+        AssignmentCode::Announcer => StaffTask::Announcer,
+        AssignmentCode::Other(v) => StaffTask::Other(v.trim_start_matches("staff-").to_string()),
+    })
+}
+
+/// Why the competitors of a round can not be found in the wcif.
+#[derive(Debug, PartialEq)]
+pub enum RoundError {
+    /// The round is not in the wcif. Rounds are numbered from 1.
+    UnknownRound { event: String, round: usize },
+    /// An accepted competitor has no registrant id, so they can not be put on a scorecard.
+    MissingRegistrantId { name: String },
+}
+
+impl Display for RoundError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoundError::UnknownRound { event, round } => write!(f, "There is no round {round} of {event} in the wcif"),
+            RoundError::MissingRegistrantId { name } => write!(f, "{name} is accepted, but has no registrant id"),
+        }
+    }
+}
+
+impl std::error::Error for RoundError {}
+
+/// Competitors of a round, best first. Later rounds take the competitors advancing from the results
+/// of the previous round, and first rounds the registered competitors by their personal bests.
+pub fn get_competitors_for_round(wcif: &mut WcifContainer, event: &str, round: usize) -> std::result::Result<(Vec<usize>, HashMap<usize, String>), RoundError> {
+    let unknown = |round| RoundError::UnknownRound { event: event.to_string(), round };
+    if get_round_json(wcif, event, round).is_none() {
+        return Err(unknown(round));
+    }
+    let id_map = get_id_map(wcif);
+    let advancement_ids = match round {
+        1 => {
+            let mut registered = vec![];
+            for p in wcif.persons_iter() {
+                let reg = match p.registration.as_ref() {
+                    None => continue,
+                    Some(v) => v
+                };
+                if reg.status == "accepted" && reg.event_ids.contains(&event.to_string()) {
+                    let id = p.registrant_id.ok_or_else(|| RoundError::MissingRegistrantId { name: p.name.clone() })?;
+                    registered.push((seed_value(p, event), id));
                 }
             }
+            registered.sort();
+            registered.into_iter().map(|(_, id)| id).collect()
+        }
+        round => {
+            let previous = format!("{}-r{}", event, round - 1);
+            let previous = wcif.events_iter().flat_map(|e| &e.rounds).find(|round| round.id == previous).ok_or_else(|| unknown(round - 1))?;
+            ResultsStore::import(wcif.get()).advancing(previous)
+        }
+    };
+    Ok((advancement_ids, id_map))
+}
+
+/// Personal best average and single of a person in an event, for seeding first rounds. Blindfolded
+/// events are seeded by single. Competitors without a result come last.
+fn seed_value(person: &Person, event: &str) -> (usize, usize) {
+    let best = |t: &str| person.personal_bests.iter()
+        .find(|pb| pb.event_id == event && pb.t == t)
+        .and_then(|pb| match pb.best {
+            AttemptResult::Ok(v) => Some(v),
+            _ => None
         })
+        .unwrap_or(usize::MAX);
+    match event {
+        "333bf" | "444bf" | "555bf" | "333mbf" => (best("single"), 0),
+        _ => (best("average"), best("single"))
     }
 }
 
+pub(crate) fn get_round_json<'a>(wcif: &'a mut WcifContainer, event: &str, round: usize) -> Option<&'a mut Round> {
+    let activity_id = format!("{}-r{}", event, round);
+    wcif.round_iter_mut().find(|round| round.id == activity_id)
+}
+
+/// Names and colours of the rooms holding a round, in the order of the schedule. A round held on
+/// several stages is usually in a room per stage.
+pub fn get_round_rooms(wcif: &WcifContainer, event: &str, round: usize) -> Vec<StageName> {
+    let code = format!("{}-r{}", event, round);
+    wcif.get().schedule.venues.iter()
+        .flat_map(|venue| &venue.rooms)
+        .filter(|room| room.activities.iter().any(|a| a.activity_code == code || a.activity_code.starts_with(&format!("{code}-"))))
+        .map(|room| StageName { name: room.name.clone(), colour: Some(room.color.clone()) })
+        .collect()
+}
+
 pub fn get_id_map(wcif: &WcifContainer) -> HashMap<usize, String> {
     wcif.persons_iter().filter_map(|p| p.registrant_id.map(|v|(v, p.name.clone()))).collect()
 }
 
-fn get_advancement_ids(round: &Round, advancement_condition: &Option<AdvancementCondition>) -> Vec<usize> {
-    let advancement_amount = get_advancement_amount(round, advancement_condition);
-    match advancement_amount {
-        None => return vec![],
-        Some(advancement_amount) => {
-            let mut intermediate = round.results.iter().collect::<Vec<_>>();
-            intermediate.sort_by_key(|r| r.ranking);
-            let filtered = intermediate.into_iter().filter(|result| result.ranking.unwrap() <= advancement_amount).collect::<Vec<_>>();
-            if filtered.len() > advancement_amount {
-                let not_included = filtered.last().unwrap().ranking.unwrap();
-                return filtered.iter().filter(|result| result.ranking.unwrap() != not_included).map(|result| result.person_id).collect();
-            }
-            filtered.iter().map(|result| result.person_id).collect()
-        }
+
+#[cfg(test)]
+mod test {
+    use wca_oauth::{WcifContainer, WcifGenerator};
+
+    use super::{get_competitors_for_round, get_round_rooms, seed_value, RoundError};
+
+    #[test]
+    fn first_round_seeding() {
+        let mut wcif = WcifContainer::new(WcifGenerator::new(1, 60).generate());
+        let (competitors, _) = get_competitors_for_round(&mut wcif, "333", 1).unwrap();
+        assert!(!competitors.is_empty());
+        let seeds: Vec<_> = competitors.iter()
+            .map(|id| seed_value(wcif.persons_iter().find(|p| p.registrant_id == Some(*id)).unwrap(), "333"))
+            .collect();
+        assert!(seeds.windows(2).all(|w| w[0] <= w[1]));
+        //Nobody advances from a round without results.
+        assert!(get_competitors_for_round(&mut wcif, "333", 2).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn invalid_rounds() {
+        let mut wcif = WcifContainer::new(WcifGenerator::new(1, 60).generate());
+        let unknown = |round| Err(RoundError::UnknownRound { event: "333".to_string(), round });
+        assert_eq!(get_competitors_for_round(&mut wcif, "333", 0), unknown(0));
+        assert_eq!(get_competitors_for_round(&mut wcif, "333", 9), unknown(9));
+        assert!(matches!(get_competitors_for_round(&mut wcif, "222bf", 1), Err(RoundError::UnknownRound { .. })));
+
+        let name = wcif.persons_iter().find(|p| p.registration.as_ref().is_some_and(|r| r.status == "accepted" && r.event_ids.contains(&"333".to_string()))).unwrap().name.clone();
+        wcif.patch_persons(|p| if p.name == name { p.registrant_id = None });
+        assert_eq!(get_competitors_for_round(&mut wcif, "333", 1), Err(RoundError::MissingRegistrantId { name }));
+    }
+
+    #[test]
+    fn round_rooms() {
+        let mut generator = WcifGenerator::new(1, 60);
+        generator.rooms = 2;
+        let wcif = WcifContainer::new(generator.generate());
+        let rooms = get_round_rooms(&wcif, "333", 1);
+        assert_eq!(rooms.len(), 1);
+        assert!(rooms[0].name.starts_with("Room"));
+        assert!(rooms[0].colour.as_ref().unwrap().starts_with('#'));
+        assert!(get_round_rooms(&wcif, "333", 9).is_empty());
     }
 }
@@ -0,0 +1,39 @@
+use wca_oauth::Wcif;
+
+fn main() {
+    let mut json = false;
+    let mut files = vec![];
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => files.push(arg)
+        }
+    }
+    if files.len() != 2 {
+        eprintln!("Usage: wcif_diff [--json] <old wcif> <new wcif>");
+        std::process::exit(2);
+    }
+    let old = read_wcif(&files[0]);
+    let new = read_wcif(&files[1]);
+    let diff = old.diff(&new);
+    if json {
+        println!("{}", diff.to_json());
+    }
+    else {
+        print!("{}", diff);
+    }
+    if !diff.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Exits with 2 as for wrong usage if the file can not be read, as 1 means the files differ.
+fn read_wcif(path: &str) -> Wcif {
+    let json = std::fs::read_to_string(path).unwrap_or_else(|e| fail(format!("Unable to read {}: {}", path, e)));
+    serde_json::from_str(&json).unwrap_or_else(|e| fail(format!("{} is not a valid wcif: {}", path, e)))
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}